@@ -223,7 +223,7 @@ impl ApplicationRunner {
 
             // TODO: These calculations are now repeated in three places, should probably be moved
             //       to a function
-            cx.style().dpi_factor = self.window_scale_factor * self.current_user_scale_factor;
+            cx.set_dpi_factor(self.window_scale_factor * self.current_user_scale_factor);
             cx.style()
                 .width
                 .insert(Entity::root(), Units::Pixels(self.current_window_size.width as f32));
@@ -404,7 +404,8 @@ impl ApplicationRunner {
                         self.window_scale_factor = window_info.scale();
                     }
 
-                    cx.style().dpi_factor = self.window_scale_factor * *cx.user_scale_factor();
+                    let dpi_factor = self.window_scale_factor * *cx.user_scale_factor();
+                    cx.set_dpi_factor(dpi_factor);
 
                     // Since we apply a user scale factor, our logical size may not match baseview's
                     // logical size
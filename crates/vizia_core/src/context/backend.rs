@@ -168,6 +168,16 @@ impl<'a> BackendContext<'a> {
         self.0.clipboard = clipboard;
     }
 
+    /// You should not call this method unless you are writing a windowing backend, in which case
+    /// you should consult the existing windowing backends for usage information.
+    #[cfg(feature = "clipboard")]
+    pub fn set_primary_selection_provider(
+        &mut self,
+        primary_selection: Box<dyn ClipboardProvider>,
+    ) {
+        self.0.primary_selection = primary_selection;
+    }
+
     /// Send an event with custom origin and propagation information.
     pub fn send_event(&mut self, event: Event) {
         self.0.event_queue.push_back(event);
@@ -294,4 +304,18 @@ impl<'a> BackendContext<'a> {
     pub fn needs_refresh(&mut self) {
         self.0.style.system_flags = SystemFlags::all();
     }
+
+    /// Sets the DPI factor of the window, notifying every view with a
+    /// [`WindowEvent::ScaleFactorChanged`] if it differs from the previous factor.
+    pub fn set_dpi_factor(&mut self, dpi_factor: f64) {
+        if self.0.style.dpi_factor != dpi_factor {
+            self.0.style.dpi_factor = dpi_factor;
+            self.0.event_queue.push_back(
+                Event::new(WindowEvent::ScaleFactorChanged)
+                    .target(Entity::root())
+                    .origin(Entity::root())
+                    .propagate(Propagation::Subtree),
+            );
+        }
+    }
 }
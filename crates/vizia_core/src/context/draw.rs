@@ -16,6 +16,10 @@ use crate::text::{TextConfig, TextContext};
 use vizia_input::{Modifiers, MouseState};
 use vizia_storage::SparseSet;
 
+/// The color drawn behind text ranges highlighted by a search.
+const MATCH_HIGHLIGHT_COLOR: Color = Color::rgba(255, 213, 0, 96);
+const MISSPELLING_COLOR: Color = Color::rgb(230, 30, 30);
+
 /// Cached data used for drawing.
 pub struct DrawCache {
     pub shadow_image: SparseSet<(ImageId, ImageId)>,
@@ -147,7 +151,9 @@ impl<'a> DrawContext<'a> {
     style_getter_untranslated!(Color, outer_shadow_color);
     style_getter_untranslated!(Color, inner_shadow_color);
     style_getter_untranslated!(Color, selection_color);
+    style_getter_untranslated!(Color, selection_color_inactive);
     style_getter_untranslated!(Color, caret_color);
+    style_getter_untranslated!(Units, caret_width);
     style_getter_untranslated!(LinearGradient, background_gradient);
     style_getter_untranslated!(BorderCornerShape, border_shape_top_right);
     style_getter_untranslated!(BorderCornerShape, border_shape_top_left);
@@ -186,7 +192,61 @@ impl<'a> DrawContext<'a> {
         origin: (f32, f32),
         justify: (f32, f32),
     ) {
-        if let Some(color) = self.selection_color().copied() {
+        let matches = self.text_context.layout_match_highlights(self.current, origin, justify);
+        if !matches.is_empty() {
+            let mut path = Path::new();
+            for (x, y, w, h) in matches {
+                path.rect(x, y, w, h);
+            }
+            canvas.fill_path(&mut path, &Paint::color(MATCH_HIGHLIGHT_COLOR.into()));
+        }
+
+        let preedit = self.text_context.layout_preedit(
+            self.current,
+            origin,
+            justify,
+            self.logical_to_physical(1.0),
+        );
+        if !preedit.is_empty() {
+            let color = self.font_color().copied().unwrap_or_default();
+            let mut path = Path::new();
+            for (x, y, w, h) in preedit {
+                path.rect(x, y, w, h);
+            }
+            canvas.fill_path(&mut path, &Paint::color(color.into()));
+        }
+
+        let misspellings = self.text_context.layout_misspellings(
+            self.current,
+            origin,
+            justify,
+            self.logical_to_physical(3.0),
+        );
+        if !misspellings.is_empty() {
+            // A zigzag spanning the height of each area, with one peak every two heights.
+            let mut path = Path::new();
+            for (x, y, w, h) in misspellings {
+                path.move_to(x, y + h);
+                let mut px = x;
+                let mut up = true;
+                while px < x + w {
+                    px = (px + h).min(x + w);
+                    path.line_to(px, if up { y } else { y + h });
+                    up = !up;
+                }
+            }
+            let mut paint = Paint::color(MISSPELLING_COLOR.into());
+            paint.set_line_width(self.logical_to_physical(1.0));
+            canvas.stroke_path(&mut path, &paint);
+        }
+
+        let focused = self.current.parent_iter(self.tree).any(|entity| entity == *self.focused);
+        let color = if focused {
+            self.selection_color()
+        } else {
+            self.selection_color_inactive().or_else(|| self.selection_color())
+        };
+        if let Some(color) = color.copied() {
             let mut path = Path::new();
             for (x, y, w, h) in self.text_context.layout_selection(self.current, origin, justify) {
                 path.rect(x, y, w, h);
@@ -202,6 +262,12 @@ impl<'a> DrawContext<'a> {
         justify: (f32, f32),
         width: f32,
     ) {
+        // The caret width set by the style takes precedence over the given width.
+        let width = match self.caret_width() {
+            Some(Units::Pixels(caret_width)) => *caret_width,
+            _ => width,
+        };
+
         if let Some(color) = self.caret_color().copied() {
             if let Some((x, y, w, h)) = self.text_context.layout_caret(
                 self.current,
@@ -213,6 +279,32 @@ impl<'a> DrawContext<'a> {
                 path.rect(x, y, w, h);
                 canvas.fill_path(&mut path, &Paint::color(color.into()));
             }
+
+            let extra_carets = self.text_context.layout_extra_carets(
+                self.current,
+                origin,
+                justify,
+                self.logical_to_physical(width),
+            );
+            if !extra_carets.is_empty() {
+                let mut path = Path::new();
+                for (x, y, w, h) in extra_carets {
+                    path.rect(x, y, w, h);
+                }
+                canvas.fill_path(&mut path, &Paint::color(color.into()));
+            }
+        }
+
+        if let Some((x, y, w, h)) = self.text_context.layout_drop_caret(
+            self.current,
+            origin,
+            justify,
+            self.logical_to_physical(width),
+        ) {
+            let color = self.caret_color().or(self.font_color()).copied().unwrap_or_default();
+            let mut path = Path::new();
+            path.rect(x, y, w, h);
+            canvas.fill_path(&mut path, &Paint::color(color.into()));
         }
     }
 }
@@ -46,6 +46,8 @@ pub struct EventContext<'a> {
     user_scale_factor: &'a mut f64,
     #[cfg(feature = "clipboard")]
     clipboard: &'a mut Box<dyn ClipboardProvider>,
+    #[cfg(feature = "clipboard")]
+    primary_selection: &'a mut Box<dyn ClipboardProvider>,
     event_proxy: &'a mut Option<Box<dyn crate::context::EventProxy>>,
 }
 
@@ -74,6 +76,8 @@ impl<'a> EventContext<'a> {
             user_scale_factor: &mut cx.user_scale_factor,
             #[cfg(feature = "clipboard")]
             clipboard: &mut cx.clipboard,
+            #[cfg(feature = "clipboard")]
+            primary_selection: &mut cx.primary_selection,
             event_proxy: &mut cx.event_proxy,
         }
     }
@@ -212,6 +216,15 @@ impl<'a> EventContext<'a> {
         }
     }
 
+    /// Returns true if the current entity is checked.
+    pub fn is_checked(&self) -> bool {
+        if let Some(pseudo_classes) = self.style.pseudo_classes.get(self.current) {
+            pseudo_classes.contains(PseudoClass::CHECKED)
+        } else {
+            false
+        }
+    }
+
     /// Prevents the cursor icon from changing until the lock is released.
     pub fn lock_cursor_icon(&mut self) {
         *self.cursor_icon_locked = true;
@@ -277,6 +290,25 @@ impl<'a> EventContext<'a> {
         self.clipboard.set_contents(text)
     }
 
+    /// Get the contents of the primary selection, the text most recently selected in any
+    /// application on X11 and Wayland. On other platforms the primary selection is always empty.
+    #[cfg(feature = "clipboard")]
+    pub fn get_primary_selection(
+        &mut self,
+    ) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+        self.primary_selection.get_contents()
+    }
+
+    /// Set the contents of the primary selection. This may fail for a variety of backend-specific
+    /// reasons.
+    #[cfg(feature = "clipboard")]
+    pub fn set_primary_selection(
+        &mut self,
+        text: String,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        self.primary_selection.set_contents(text)
+    }
+
     pub fn toggle_class(&mut self, class_name: &str, applied: bool) {
         let current = self.current();
         if let Some(class_list) = self.style.classes.get_mut(current) {
@@ -5,13 +5,26 @@ mod event;
 mod proxy;
 mod resource;
 
-use instant::Instant;
+use instant::{Duration, Instant};
 use std::any::{Any, TypeId};
 use std::collections::{HashMap, VecDeque};
 use std::iter::once;
 use std::path::Path;
 use std::sync::Mutex;
 
+// copypasta only provides the X11 primary selection on these targets.
+#[cfg(all(
+    feature = "clipboard",
+    feature = "x11",
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+))]
+use copypasta::x11_clipboard::{Primary, X11ClipboardContext};
 #[cfg(all(feature = "clipboard", feature = "x11"))]
 use copypasta::ClipboardContext;
 #[cfg(feature = "clipboard")]
@@ -101,10 +114,15 @@ pub struct Context {
 
     #[cfg(feature = "clipboard")]
     pub(crate) clipboard: Box<dyn ClipboardProvider>,
+    #[cfg(feature = "clipboard")]
+    pub(crate) primary_selection: Box<dyn ClipboardProvider>,
 
     pub(crate) click_time: Instant,
     pub(crate) clicks: usize,
     pub(crate) click_pos: (f32, f32),
+    pub(crate) multi_click_interval: Duration,
+    pub(crate) multi_click_distance: f32,
+    pub(crate) clock: Box<dyn Fn() -> Instant>,
 
     pub ignore_default_theme: bool,
     pub window_has_focus: bool,
@@ -185,9 +203,41 @@ impl Context {
                 #[cfg(not(feature = "x11"))]
                 Box::new(NopClipboardContext::new().unwrap())
             },
+            #[cfg(feature = "clipboard")]
+            primary_selection: {
+                #[cfg(all(
+                    feature = "x11",
+                    unix,
+                    not(any(
+                        target_os = "macos",
+                        target_os = "android",
+                        target_os = "ios",
+                        target_os = "emscripten"
+                    ))
+                ))]
+                if let Ok(context) = X11ClipboardContext::<Primary>::new() {
+                    Box::new(context)
+                } else {
+                    Box::new(NopClipboardContext::new().unwrap())
+                }
+                #[cfg(not(all(
+                    feature = "x11",
+                    unix,
+                    not(any(
+                        target_os = "macos",
+                        target_os = "android",
+                        target_os = "ios",
+                        target_os = "emscripten"
+                    ))
+                )))]
+                Box::new(NopClipboardContext::new().unwrap())
+            },
             click_time: Instant::now(),
             clicks: 0,
             click_pos: (0.0, 0.0),
+            multi_click_interval: Duration::from_millis(500),
+            multi_click_distance: 0.0,
+            clock: Box::new(Instant::now),
 
             ignore_default_theme: false,
             window_has_focus: true,
@@ -467,6 +517,21 @@ impl Context {
             .collect();
     }
 
+    /// Sets how quickly and how close together, in physical pixels, presses of a mouse button must
+    /// follow each other to count as a double or triple click, such as those which select a word or
+    /// a paragraph of a textbox. Defaults to 500 milliseconds and no movement at all.
+    pub fn set_multi_click_threshold(&mut self, interval: Duration, distance: f32) {
+        self.multi_click_interval = interval;
+        self.multi_click_distance = distance;
+    }
+
+    /// Sets the function which reads the current time when telling single clicks from double and
+    /// triple clicks, so that tests can simulate them without waiting. Defaults to
+    /// [`Instant::now`].
+    pub fn set_clock(&mut self, clock: impl Fn() -> Instant + 'static) {
+        self.clock = Box::new(clock);
+    }
+
     pub fn add_theme(&mut self, theme: &str) {
         self.resource_manager.themes.push(theme.to_owned());
 
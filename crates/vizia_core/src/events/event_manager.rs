@@ -5,14 +5,11 @@ use crate::prelude::*;
 use crate::systems::compute_matched_rules;
 use crate::systems::hover_system;
 use crate::tree::{focus_backward, focus_forward, is_navigatable};
-use instant::{Duration, Instant};
 use std::any::Any;
 use vizia_id::GenerationalId;
 use vizia_storage::TreeExt;
 use vizia_storage::TreeIterator;
 
-const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
-
 /// Dispatches events to views and models.
 ///
 /// The [EventManager] is responsible for taking the events in the event queue in context
@@ -248,10 +245,13 @@ fn internal_state_updates(context: &mut Context, window_event: &WindowEvent, met
             }
 
             // track double-click
-            let new_click_time = Instant::now();
+            let new_click_time = (context.clock)();
             let click_duration = new_click_time - context.click_time;
             let new_click_pos = (context.mouse.cursorx, context.mouse.cursory);
-            if click_duration <= DOUBLE_CLICK_INTERVAL && new_click_pos == context.click_pos {
+            let near = (new_click_pos.0 - context.click_pos.0).abs()
+                <= context.multi_click_distance
+                && (new_click_pos.1 - context.click_pos.1).abs() <= context.multi_click_distance;
+            if click_duration <= context.multi_click_interval && near {
                 if context.clicks <= 2 {
                     context.clicks += 1;
                     let event = if context.clicks == 3 {
@@ -316,6 +316,13 @@ fn internal_state_updates(context: &mut Context, window_event: &WindowEvent, met
         WindowEvent::MouseScroll(_, _) => {
             meta.target = context.hovered;
         }
+        WindowEvent::DragOver(_) | WindowEvent::DragLeave | WindowEvent::Drop(_) => {
+            // Only the hovered view is told about a drag, so a view the drag has moved off of or
+            // which ignored the drop would otherwise keep showing where it would have landed.
+            context.text_context.clear_drop_carets();
+            context.needs_redraw();
+            meta.target = context.hovered;
+        }
         WindowEvent::KeyDown(code, _) => {
             meta.target = context.focused;
 
@@ -538,7 +545,7 @@ fn internal_state_updates(context: &mut Context, window_event: &WindowEvent, met
                 context.triggered = Entity::null();
             }
         }
-        WindowEvent::CharInput(_) => {
+        WindowEvent::CharInput(_) | WindowEvent::ImePreedit(..) | WindowEvent::ImeCommit(_) => {
             meta.target = context.focused;
         }
         WindowEvent::FocusOut => {
@@ -61,9 +61,11 @@ pub mod prelude {
     pub use accesskit::{Action, DefaultActionVerb, Live, Role};
     pub use vizia_derive::{Data, Lens, Model, Setter};
     pub use vizia_id::GenerationalId;
-    pub use vizia_input::{Code, Key, KeyChord, Modifiers, MouseButton, MouseButtonState};
+    pub use vizia_input::{
+        command_modifier, Code, Key, KeyChord, Modifiers, MouseButton, MouseButtonState,
+    };
     pub use vizia_storage::{Tree, TreeExt};
-    pub use vizia_window::{CursorIcon, WindowDescription, WindowEvent, WindowSize};
+    pub use vizia_window::{CursorIcon, DropData, WindowDescription, WindowEvent, WindowSize};
 
     pub use super::style::{
         Abilities, BorderCornerShape, Color, Display, GradientDirection, GradientStop,
@@ -66,6 +66,7 @@ where
 pub struct Localized {
     key: String,
     args: HashMap<String, Box<dyn FluentStore>>,
+    fallback: Option<String>,
 }
 
 pub enum LocalizedArg {
@@ -78,6 +79,7 @@ impl Clone for Localized {
         Self {
             key: self.key.clone(),
             args: self.args.iter().map(|(k, v)| (k.clone(), v.make_clone())).collect(),
+            fallback: self.fallback.clone(),
         }
     }
 }
@@ -91,8 +93,20 @@ impl Localized {
         res
     }
 
+    fn missing(&self) -> String {
+        self.fallback.clone().unwrap_or_else(|| format!("{{MISSING: {}}}", self.key))
+    }
+
     pub fn new(key: &str) -> Self {
-        Self { key: key.to_owned(), args: HashMap::new() }
+        Self { key: key.to_owned(), args: HashMap::new(), fallback: None }
+    }
+
+    /// Sets the text shown when the current translation has no message for the key, in place of
+    /// the missing message placeholder. Used by built-in views so that they read correctly
+    /// without any translations while still allowing applications to translate them.
+    pub fn fallback(mut self, text: &str) -> Self {
+        self.fallback = Some(text.to_owned());
+        self
     }
 
     pub fn arg<L>(mut self, key: &str, lens: L) -> Self
@@ -117,13 +131,13 @@ impl Res<String> for Localized {
         let message = if let Some(msg) = bundle.get_message(&self.key) {
             msg
         } else {
-            return format!("{{MISSING: {}}}", self.key);
+            return self.missing();
         };
 
         let value = if let Some(value) = message.value() {
             value
         } else {
-            return format!("{{MISSING: {}}}", self.key);
+            return self.missing();
         };
 
         let mut err = vec![];
@@ -30,14 +30,14 @@ pub trait TextModifiers: internal::Modifiable {
         /// Sets the font weight that should be used by the view.
         font_weight,
         Weight,
-        SystemFlags::REDRAW
+        SystemFlags::REFLOW
     );
 
     modifier!(
         /// Sets the font style that should be used by the view.
         font_style,
         FontStyle,
-        SystemFlags::REDRAW
+        SystemFlags::REFLOW
     );
 
     /// Sets the text color of the view.
@@ -67,6 +67,14 @@ pub trait TextModifiers: internal::Modifiable {
         SystemFlags::REDRAW
     );
 
+    modifier!(
+        /// Sets the width of the text caret of the view. Only pixel values are supported, and the
+        /// caret is one pixel wide by default.
+        caret_width,
+        Units,
+        SystemFlags::REDRAW
+    );
+
     modifier!(
         /// Sets the color used to highlight selected text within the view.
         selection_color,
@@ -74,6 +82,14 @@ pub trait TextModifiers: internal::Modifiable {
         SystemFlags::REDRAW
     );
 
+    modifier!(
+        /// Sets the color used to highlight selected text while the view, or the view containing it,
+        /// is not focused. Defaults to the selection color.
+        selection_color_inactive,
+        Color,
+        SystemFlags::REDRAW
+    );
+
     modifier!(
         /// Sets whether the text of the view should be allowed to wrap.
         text_wrap,
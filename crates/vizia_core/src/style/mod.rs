@@ -204,7 +204,9 @@ pub struct Style {
     pub font_weight: StyleSet<Weight>,
     pub font_style: StyleSet<FontStyle>,
     pub caret_color: AnimatableSet<Color>,
+    pub caret_width: AnimatableSet<Units>,
     pub selection_color: AnimatableSet<Color>,
+    pub selection_color_inactive: AnimatableSet<Color>,
 
     // Image
     pub image: StyleSet<String>,
@@ -561,10 +563,18 @@ impl Style {
                         self.selection_color.insert_rule(rule_id, value);
                     }
 
+                    Property::SelectionColorInactive(value) => {
+                        self.selection_color_inactive.insert_rule(rule_id, value);
+                    }
+
                     Property::CaretColor(value) => {
                         self.caret_color.insert_rule(rule_id, value);
                     }
 
+                    Property::CaretWidth(value) => {
+                        self.caret_width.insert_rule(rule_id, value);
+                    }
+
                     // Background
                     Property::BackgroundColor(value) => {
                         self.background_color.insert_rule(rule_id, value);
@@ -1144,7 +1154,9 @@ impl Style {
         self.font_color.remove(entity);
         self.font_size.remove(entity);
         self.selection_color.remove(entity);
+        self.selection_color_inactive.remove(entity);
         self.caret_color.remove(entity);
+        self.caret_width.remove(entity);
 
         self.cursor.remove(entity);
 
@@ -1288,7 +1300,9 @@ impl Style {
         self.font_color.clear_rules();
         self.font_size.clear_rules();
         self.selection_color.clear_rules();
+        self.selection_color_inactive.clear_rules();
         self.caret_color.clear_rules();
+        self.caret_width.clear_rules();
 
         self.cursor.clear_rules();
 
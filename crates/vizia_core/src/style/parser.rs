@@ -408,7 +408,9 @@ impl<'i> cssparser::DeclarationParser<'i> for DeclarationParser {
             "font-style" => Property::FontStyle(parse_font_style(input)?),
             "text-wrap" => Property::TextWrap(parse_bool(input)?),
             "selection-color" => Property::SelectionColor(parse_color(input)?),
+            "selection-color-inactive" => Property::SelectionColorInactive(parse_color(input)?),
             "caret-color" => Property::CaretColor(parse_color(input)?),
+            "caret-width" => Property::CaretWidth(parse_units(input)?),
 
             // Border
             "border-width" => Property::BorderWidth(parse_units(input)?),
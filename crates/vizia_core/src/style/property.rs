@@ -96,7 +96,9 @@ pub(crate) enum Property {
     FontWeight(Weight),
     FontStyle(FontStyle),
     SelectionColor(Color),
+    SelectionColorInactive(Color),
     CaretColor(Color),
+    CaretWidth(Units),
     TextWrap(bool),
 
     // Shadow
@@ -253,7 +255,11 @@ impl std::fmt::Display for Property {
             Property::FontWeight(val) => write!(f, "font-weight: {}", val.0),
             Property::FontStyle(val) => write!(f, "font-style: {}", fmt_font_style(val)),
             Property::SelectionColor(val) => write!(f, "selection-color: {}", val),
+            Property::SelectionColorInactive(val) => {
+                write!(f, "selection-color-inactive: {}", val)
+            }
             Property::CaretColor(val) => write!(f, "caret-color: {}", val),
+            Property::CaretWidth(val) => write!(f, "caret-width: {}", fmt_units(val)),
             Property::TextWrap(val) => write!(f, "text-wrap: {}", val),
 
             // Shadow
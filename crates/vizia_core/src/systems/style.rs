@@ -15,7 +15,9 @@ pub fn inline_inheritance_system(cx: &mut Context) {
             cx.style.font_weight.inherit_inline(entity, parent);
             cx.style.font_style.inherit_inline(entity, parent);
             cx.style.caret_color.inherit_inline(entity, parent);
+            cx.style.caret_width.inherit_inline(entity, parent);
             cx.style.selection_color.inherit_inline(entity, parent);
+            cx.style.selection_color_inactive.inherit_inline(entity, parent);
         }
     }
 }
@@ -29,7 +31,9 @@ pub fn shared_inheritance_system(cx: &mut Context) {
             cx.style.font_weight.inherit_shared(entity, parent);
             cx.style.font_style.inherit_shared(entity, parent);
             cx.style.caret_color.inherit_shared(entity, parent);
+            cx.style.caret_width.inherit_shared(entity, parent);
             cx.style.selection_color.inherit_shared(entity, parent);
+            cx.style.selection_color_inactive.inherit_shared(entity, parent);
         }
     }
 }
@@ -377,10 +381,18 @@ fn link_style_data(style: &mut Style, entity: Entity, matched_rules: &Vec<Rule>)
         should_redraw = true;
     }
 
+    if style.selection_color_inactive.link(entity, matched_rules) {
+        should_redraw = true;
+    }
+
     if style.caret_color.link(entity, matched_rules) {
         should_redraw = true;
     }
 
+    if style.caret_width.link(entity, matched_rules) {
+        should_redraw = true;
+    }
+
     // Outer Shadow
     if style.outer_shadow_h_offset.link(entity, matched_rules) {
         should_redraw = true;
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use crate::style::SystemFlags;
+use crate::text::{widened_spaces, widened_width, Spacing};
 use vizia_id::GenerationalId;
 use vizia_storage::DrawIterator;
 
@@ -77,11 +78,14 @@ pub fn text_constraints_system(cx: &mut Context) {
 
             if cx.text_context.has_buffer(entity) {
                 cx.text_context.sync_styles(entity, &cx.style);
+                // Tabs advanced to tab stops widen the text, justified spaces don't.
+                let spacing = Spacing { justified: false, ..cx.text_context.spacing(entity) };
                 let (text_width, text_height) = cx.text_context.with_buffer(entity, |buf| {
                     buf.set_size(999999, i32::MAX);
                     let w = buf
                         .layout_runs()
-                        .filter_map(|r| (!r.line_w.is_nan()).then_some(r.line_w))
+                        .map(|r| widened_width(&r, &widened_spaces(buf, &r, spacing)))
+                        .filter(|w| !w.is_nan())
                         .max_by(|f1, f2| f1.partial_cmp(f2).unwrap())
                         .unwrap_or_default();
                     let h = buf.layout_runs().len() as f32 * buf.metrics().line_height as f32;
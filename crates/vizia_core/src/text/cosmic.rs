@@ -3,8 +3,9 @@ use crate::prelude::Color;
 use crate::style::Style;
 use cosmic_text::{
     fontdb::{Database, Query},
-    Attrs, AttrsList, Buffer, CacheKey, Color as FontColor, Color as CosmicColor, Edit, Editor,
-    Family, FontSystem, Metrics, SubpixelBin, Wrap,
+    Action, Attrs, AttrsList, AttrsOwned, Buffer, CacheKey, Color as FontColor,
+    Color as CosmicColor, Cursor, Edit, Editor, Family, FontSystem, LayoutGlyph, LayoutRun,
+    Metrics, Style as FontStyle, SubpixelBin, Weight, Wrap,
 };
 use femtovg::imgref::{Img, ImgRef};
 use femtovg::rgb::RGBA8;
@@ -14,10 +15,12 @@ use femtovg::{
 };
 use fnv::FnvHashMap;
 use ouroboros::self_referencing;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use swash::scale::image::Content;
 use swash::scale::{Render, ScaleContext, Source, StrikeWith};
 use swash::zeno::{Format, Vector};
+use unicode_segmentation::UnicodeSegmentation;
 
 const GLYPH_PADDING: u32 = 1;
 const GLYPH_MARGIN: u32 = 1;
@@ -50,6 +53,17 @@ struct TextContextInternal<'a> {
     rendered_glyphs: FnvHashMap<CacheKey, Option<RenderedGlyph>>,
     glyph_textures: Vec<FontTexture>,
     buffers: HashMap<Entity, Editor<'a>>,
+    spans: HashMap<Entity, Vec<(Range<usize>, AttrsOwned)>>,
+    match_highlights: HashMap<Entity, Vec<Range<usize>>>,
+    drop_carets: HashMap<Entity, usize>,
+    block_carets: HashSet<Entity>,
+    spacings: HashMap<Entity, Spacing>,
+    extra_cursors: HashMap<Entity, Vec<(usize, usize)>>,
+    preedits: HashMap<Entity, Range<usize>>,
+    misspellings: HashMap<Entity, Vec<Range<usize>>>,
+    edited_ranges: HashMap<Entity, Range<usize>>,
+    synced_attrs: HashMap<Entity, AttrsOwned>,
+    average_advances: HashMap<(AttrsOwned, i32), f32>,
 }
 
 impl TextContext {
@@ -61,6 +75,16 @@ impl TextContext {
     pub fn clear_buffer(&mut self, entity: Entity) {
         self.with_int_mut(move |int: &mut TextContextInternal| {
             int.buffers.remove(&entity);
+            int.spans.remove(&entity);
+            int.match_highlights.remove(&entity);
+            int.drop_carets.remove(&entity);
+            int.block_carets.remove(&entity);
+            int.spacings.remove(&entity);
+            int.misspellings.remove(&entity);
+            int.edited_ranges.remove(&entity);
+            int.synced_attrs.remove(&entity);
+            int.extra_cursors.remove(&entity);
+            int.preedits.remove(&entity);
         });
     }
 
@@ -72,6 +96,206 @@ impl TextContext {
         self.with_buffer(entity, |buf| {
             buf.set_text(text, Attrs::new());
         });
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            int.synced_attrs.remove(&entity);
+        });
+    }
+
+    /// Sets styled spans over the text of an entity. Ranges are byte offsets into the text with
+    /// lines joined by a single `\n`. Any attributes left at their defaults fall back to the styles
+    /// of the entity.
+    pub fn set_spans(&mut self, entity: Entity, spans: Vec<(Range<usize>, AttrsOwned)>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            int.synced_attrs.remove(&entity);
+            if spans.is_empty() {
+                int.spans.remove(&entity);
+            } else {
+                int.spans.insert(entity, spans);
+            }
+        });
+    }
+
+    pub fn clear_spans(&mut self, entity: Entity) {
+        self.set_spans(entity, Vec::new());
+    }
+
+    /// Sets byte ranges of the text of an entity to be drawn with a highlight behind them, such as
+    /// the results of a search.
+    pub fn set_match_highlights(&mut self, entity: Entity, ranges: Vec<Range<usize>>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            if ranges.is_empty() {
+                int.match_highlights.remove(&entity);
+            } else {
+                int.match_highlights.insert(entity, ranges);
+            }
+        });
+    }
+
+    pub fn match_highlights(&self, entity: Entity) -> Vec<Range<usize>> {
+        self.with_int(move |int: &TextContextInternal| {
+            int.match_highlights.get(&entity).cloned().unwrap_or_default()
+        })
+    }
+
+    /// Sets the byte offset into the text of an entity at which a caret is drawn to show where
+    /// dragged data would be dropped, or `None` to hide it.
+    pub fn set_drop_caret(&mut self, entity: Entity, offset: Option<usize>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            if let Some(offset) = offset {
+                int.drop_carets.insert(entity, offset);
+            } else {
+                int.drop_carets.remove(&entity);
+            }
+        });
+    }
+
+    /// Returns the byte offset at which the drop caret of an entity is drawn, if it is shown.
+    pub fn drop_caret(&self, entity: Entity) -> Option<usize> {
+        self.with_int(move |int: &TextContextInternal| int.drop_carets.get(&entity).copied())
+    }
+
+    /// Hides the drop carets of all entities, for example when a drag ends.
+    pub fn clear_drop_carets(&mut self) {
+        self.with_int_mut(|int: &mut TextContextInternal| int.drop_carets.clear());
+    }
+
+    /// Sets whether the caret of an entity is drawn as a block covering the grapheme cluster after
+    /// it, as used to show overwrite mode.
+    pub fn set_block_caret(&mut self, entity: Entity, flag: bool) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            if flag {
+                int.block_carets.insert(entity);
+            } else {
+                int.block_carets.remove(&entity);
+            }
+        });
+    }
+
+    /// Sets whether the wrapped lines of the text of an entity are stretched to the width of its
+    /// buffer by widening the spaces between words. The last line of each paragraph is left as is.
+    pub fn set_justified(&mut self, entity: Entity, flag: bool) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            int.spacings.entry(entity).or_default().justified = flag;
+        });
+    }
+
+    /// Sets the distance between tab stops of the text of an entity in ems, to which tab characters
+    /// advance, or `None` to draw tab characters at the width the font gives them.
+    pub fn set_tab_stop(&mut self, entity: Entity, tab_stop: Option<f32>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            int.spacings.entry(entity).or_default().tab_stop = tab_stop;
+        });
+    }
+
+    /// Returns how the whitespace of the text of an entity is widened when it is drawn.
+    pub(crate) fn spacing(&self, entity: Entity) -> Spacing {
+        self.with_int(move |int: &TextContextInternal| {
+            int.spacings.get(&entity).copied().unwrap_or_default()
+        })
+    }
+
+    /// Sets the carets drawn in addition to the cursor of the editor of an entity, as pairs of
+    /// selection anchor and caret byte offsets into its text.
+    pub fn set_extra_cursors(&mut self, entity: Entity, cursors: Vec<(usize, usize)>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            if cursors.is_empty() {
+                int.extra_cursors.remove(&entity);
+            } else {
+                int.extra_cursors.insert(entity, cursors);
+            }
+        });
+    }
+
+    pub fn extra_cursors(&self, entity: Entity) -> Vec<(usize, usize)> {
+        self.with_int(move |int: &TextContextInternal| {
+            int.extra_cursors.get(&entity).cloned().unwrap_or_default()
+        })
+    }
+
+    /// Sets the byte range of the text of an entity which an input method is composing, drawn
+    /// underlined, or `None` if there is no composition.
+    pub fn set_preedit(&mut self, entity: Entity, range: Option<Range<usize>>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            if let Some(range) = range {
+                int.preedits.insert(entity, range);
+            } else {
+                int.preedits.remove(&entity);
+            }
+        });
+    }
+
+    /// Sets byte ranges of the text of an entity which are misspelled, drawn with a wavy underline.
+    pub fn set_misspellings(&mut self, entity: Entity, ranges: Vec<Range<usize>>) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            if ranges.is_empty() {
+                int.misspellings.remove(&entity);
+            } else {
+                int.misspellings.insert(entity, ranges);
+            }
+        });
+    }
+
+    /// Returns the average advance of the characters of the font of an entity in physical pixels,
+    /// measured over the lowercase latin letters and digits. The advance is measured once for each
+    /// font and size.
+    pub fn average_advance(&mut self, entity: Entity) -> f32 {
+        const SAMPLE: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            let buffer = int.buffers.get(&entity)?.buffer();
+            let attrs = buffer.lines.first()?.attrs_list().defaults();
+            let key = (AttrsOwned::new(attrs), buffer.metrics().font_size);
+            if let Some(advance) = int.average_advances.get(&key) {
+                return Some(*advance);
+            }
+
+            let mut sample = Buffer::new(int.font_system, buffer.metrics());
+            sample.set_size(i32::MAX, i32::MAX);
+            sample.set_text(SAMPLE, attrs);
+            sample.shape_until_scroll();
+            let advance = sample.layout_runs().next()?.line_w / SAMPLE.len() as f32;
+            int.average_advances.insert(key, advance);
+            Some(advance)
+        })
+        .unwrap_or_default()
+    }
+
+    /// Shifts the styled spans, match highlights and misspellings of an entity to account for an
+    /// edit which replaced the `removed` byte range with `inserted` bytes of text.
+    /// The edited lines are remembered so that the next style sync only touches them.
+    pub fn remap_ranges(&mut self, entity: Entity, removed: Range<usize>, inserted: usize) {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            let edited = removed.start..removed.start + inserted;
+            let edited = match int.edited_ranges.get(&entity) {
+                Some(range) => {
+                    let range = remap_range(range.clone(), removed.clone(), inserted);
+                    range.start.min(edited.start)..range.end.max(edited.end)
+                }
+                None => edited,
+            };
+            int.edited_ranges.insert(entity, edited);
+
+            if let Some(spans) = int.spans.get_mut(&entity) {
+                for (range, _) in spans.iter_mut() {
+                    *range = remap_range(range.clone(), removed.clone(), inserted);
+                }
+                spans.retain(|(range, _)| !range.is_empty());
+            }
+
+            if let Some(ranges) = int.match_highlights.get_mut(&entity) {
+                for range in ranges.iter_mut() {
+                    *range = remap_range(range.clone(), removed.clone(), inserted);
+                }
+                ranges.retain(|range| !range.is_empty());
+            }
+
+            if let Some(ranges) = int.misspellings.get_mut(&entity) {
+                for range in ranges.iter_mut() {
+                    *range = remap_range(range.clone(), removed.clone(), inserted);
+                }
+                ranges.retain(|range| !range.is_empty());
+            }
+        });
     }
 
     pub fn with_editor<O>(&mut self, entity: Entity, f: impl FnOnce(&mut Editor) -> O) -> O {
@@ -87,6 +311,9 @@ impl TextContext {
         self.with_editor(entity, |ed| f(ed.buffer_mut()))
     }
 
+    /// Applies the styles of an entity to its text. If the base attributes are unchanged since the
+    /// last sync, only the lines touched by edits since then are given new attributes, so that
+    /// cosmic only reshapes those lines.
     pub fn sync_styles(&mut self, entity: Entity, style: &Style) {
         let (family, weight, font_style, monospace) = self.with_int(|int: &TextContextInternal| {
             let families = style
@@ -107,22 +334,49 @@ impl TextContext {
             (font.info.family.clone(), font.info.weight, font.info.style, font.info.monospaced)
         });
         let color = style.font_color.get(entity).copied().unwrap_or(Color::rgb(0, 0, 0));
+        let attrs = Attrs::new()
+            .family(Family::Name(&family))
+            .weight(weight)
+            .style(font_style)
+            .monospaced(monospace)
+            .color(FontColor::rgba(color.r(), color.g(), color.b(), color.a()));
+        let (spans, edited, unchanged) = self.with_int_mut(|int: &mut TextContextInternal| {
+            let attrs = AttrsOwned::new(attrs);
+            let unchanged = int.synced_attrs.get(&entity) == Some(&attrs);
+            int.synced_attrs.insert(entity, attrs);
+            (int.spans.get(&entity).cloned(), int.edited_ranges.remove(&entity), unchanged)
+        });
         self.with_buffer(entity, |buf| {
-            let attrs = Attrs::new()
-                .family(Family::Name(&family))
-                .weight(weight)
-                .style(font_style)
-                .monospaced(monospace)
-                .color(FontColor::rgba(color.r(), color.g(), color.b(), color.a()));
             let wrap = if style.text_wrap.get(entity).copied().unwrap_or(true) {
                 Wrap::Word
             } else {
                 Wrap::None
             };
             buf.set_wrap(wrap);
-            for line in buf.lines.iter_mut() {
-                // TODO spans
-                line.set_attrs_list(AttrsList::new(attrs));
+            if !unchanged || edited.is_some() {
+                let mut line_start = 0;
+                for line in buf.lines.iter_mut() {
+                    let line_end = line_start + line.text().len();
+                    let touched = !unchanged
+                        || edited.as_ref().map_or(false, |range| {
+                            range.start <= line_end && line_start <= range.end
+                        });
+                    if touched {
+                        let mut attrs_list = AttrsList::new(attrs);
+                        for (range, span_attrs) in spans.iter().flatten() {
+                            let start = range.start.max(line_start);
+                            let end = range.end.min(line_end);
+                            if start < end {
+                                attrs_list.add_span(
+                                    (start - line_start)..(end - line_start),
+                                    merge_attrs(attrs, span_attrs.as_attrs()),
+                                );
+                            }
+                        }
+                        line.set_attrs_list(attrs_list);
+                    }
+                    line_start = line_end + 1;
+                }
             }
             let font_size =
                 style.font_size.get(entity).copied().unwrap_or(16.0) * style.dpi_factor as f32;
@@ -151,12 +405,16 @@ impl TextContext {
             let mut color_cmd_map = FnvHashMap::default();
 
             let total_height = buffer.layout_runs().len() as i32 * buffer.metrics().line_height;
+            let spacing = int.spacings.get(&entity).copied().unwrap_or_default();
             for run in buffer.layout_runs() {
+                let spaces = widened_spaces(buffer, &run, spacing);
+                let line_w = widened_width(&run, &spaces);
                 for glyph in run.glyphs.iter() {
                     let mut cache_key = glyph.cache_key;
                     let position_x = position.0 + cache_key.x_bin.as_float();
+                    let position_x = position_x + widen_x(&spaces, glyph.x) - glyph.x;
                     let position_y = position.1 + cache_key.y_bin.as_float();
-                    let position_x = position_x - run.line_w * justify.0;
+                    let position_x = position_x - line_w * justify.0;
                     let position_y = position_y - total_height as f32 * justify.1;
                     let (position_x, subpixel_x) = SubpixelBin::new(position_x);
                     let (position_y, subpixel_y) = SubpixelBin::new(position_y);
@@ -294,7 +552,8 @@ impl TextContext {
         position: (f32, f32),
         justify: (f32, f32),
     ) -> Vec<(f32, f32, f32, f32)> {
-        self.with_editor(entity, |buf| {
+        let spacing = self.spacing(entity);
+        let mut result = self.with_editor(entity, |buf| {
             let mut result = vec![];
             if let Some(cursor_end) = buf.select_opt() {
                 let (cursor_start, cursor_end) = if buf.cursor() < cursor_end {
@@ -302,21 +561,77 @@ impl TextContext {
                 } else {
                     (cursor_end, buf.cursor())
                 };
-                let buffer = buf.buffer();
-                let total_height = buffer.layout_runs().len() as i32 * buffer.metrics().line_height;
-                for run in buffer.layout_runs() {
-                    if let Some((x, w)) = run.highlight(cursor_start, cursor_end) {
-                        let y = run.line_y as f32 - buffer.metrics().font_size as f32;
-                        let x = x + position.0 - run.line_w * justify.0;
-                        let y = y + position.1 - total_height as f32 * justify.1;
-                        result.push((x, y, w, buffer.metrics().line_height as f32));
-                    }
+                layout_highlight(
+                    buf.buffer(),
+                    cursor_start,
+                    cursor_end,
+                    position,
+                    justify,
+                    spacing,
+                    &mut result,
+                );
+            }
+            result
+        });
+
+        for (anchor, cursor) in self.extra_cursors(entity) {
+            self.with_buffer(entity, |buffer| {
+                let start = offset_to_cursor(buffer, anchor.min(cursor));
+                let end = offset_to_cursor(buffer, anchor.max(cursor));
+                layout_highlight(buffer, start, end, position, justify, spacing, &mut result);
+            });
+        }
+        result
+    }
+
+    pub(crate) fn layout_match_highlights(
+        &mut self,
+        entity: Entity,
+        position: (f32, f32),
+        justify: (f32, f32),
+    ) -> Vec<(f32, f32, f32, f32)> {
+        if !self.has_buffer(entity) {
+            return vec![];
+        }
+
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            let mut result = vec![];
+            if let Some(ranges) = int.match_highlights.get(&entity) {
+                let buffer = int.buffers.get(&entity).unwrap().buffer();
+                let spacing = int.spacings.get(&entity).copied().unwrap_or_default();
+                for range in ranges.iter() {
+                    let start = offset_to_cursor(buffer, range.start);
+                    let end = offset_to_cursor(buffer, range.end);
+                    layout_highlight(buffer, start, end, position, justify, spacing, &mut result);
                 }
             }
             result
         })
     }
 
+    /// Lays out the highlight rectangles of a byte range of the text of an entity, one for each
+    /// line the range covers.
+    pub(crate) fn layout_range(
+        &mut self,
+        entity: Entity,
+        range: Range<usize>,
+        position: (f32, f32),
+        justify: (f32, f32),
+    ) -> Vec<(f32, f32, f32, f32)> {
+        if !self.has_buffer(entity) {
+            return vec![];
+        }
+
+        let spacing = self.spacing(entity);
+        self.with_buffer(entity, |buffer| {
+            let mut result = vec![];
+            let start = offset_to_cursor(buffer, range.start);
+            let end = offset_to_cursor(buffer, range.end);
+            layout_highlight(buffer, start, end, position, justify, spacing, &mut result);
+            result
+        })
+    }
+
     pub(crate) fn layout_caret(
         &mut self,
         entity: Entity,
@@ -324,19 +639,105 @@ impl TextContext {
         justify: (f32, f32),
         width: f32,
     ) -> Option<(f32, f32, f32, f32)> {
+        let block =
+            self.with_int(move |int: &TextContextInternal| int.block_carets.contains(&entity));
+        let spacing = self.spacing(entity);
         self.with_editor(entity, |buf| {
-            let (cursor_start, cursor_end) = (buf.cursor(), buf.cursor());
-            let buffer = buf.buffer();
-            let total_height = buffer.layout_runs().len() as i32 * buffer.metrics().line_height;
-            for run in buffer.layout_runs() {
-                if let Some((x, _)) = run.highlight(cursor_start, cursor_end) {
-                    let y = run.line_y as f32 - buffer.metrics().font_size as f32;
-                    let x = x + position.0 - run.line_w * justify.0;
-                    let y = y + position.1 - total_height as f32 * justify.1;
-                    return Some((x - width / 2.0, y, width, buffer.metrics().line_height as f32));
+            if block {
+                layout_block_cursor(buf.buffer(), buf.cursor(), position, justify, spacing)
+            } else {
+                layout_cursor(buf.buffer(), buf.cursor(), position, justify, spacing, width)
+            }
+        })
+    }
+
+    /// Lays out an underline of the given thickness below the text being composed by an input
+    /// method.
+    pub(crate) fn layout_preedit(
+        &mut self,
+        entity: Entity,
+        position: (f32, f32),
+        justify: (f32, f32),
+        thickness: f32,
+    ) -> Vec<(f32, f32, f32, f32)> {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            let mut result = vec![];
+            if let (Some(range), Some(editor)) =
+                (int.preedits.get(&entity), int.buffers.get(&entity))
+            {
+                let buffer = editor.buffer();
+                let start = offset_to_cursor(buffer, range.start);
+                let end = offset_to_cursor(buffer, range.end);
+                let spacing = int.spacings.get(&entity).copied().unwrap_or_default();
+                layout_highlight(buffer, start, end, position, justify, spacing, &mut result);
+            }
+            result.into_iter().map(|(x, y, w, h)| (x, y + h - thickness, w, thickness)).collect()
+        })
+    }
+
+    /// Lays out the area below each misspelled range of the text in which its wavy underline of
+    /// the given height is drawn.
+    pub(crate) fn layout_misspellings(
+        &mut self,
+        entity: Entity,
+        position: (f32, f32),
+        justify: (f32, f32),
+        height: f32,
+    ) -> Vec<(f32, f32, f32, f32)> {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            let mut result = vec![];
+            if let (Some(ranges), Some(editor)) =
+                (int.misspellings.get(&entity), int.buffers.get(&entity))
+            {
+                let buffer = editor.buffer();
+                let spacing = int.spacings.get(&entity).copied().unwrap_or_default();
+                for range in ranges.iter() {
+                    let start = offset_to_cursor(buffer, range.start);
+                    let end = offset_to_cursor(buffer, range.end);
+                    layout_highlight(buffer, start, end, position, justify, spacing, &mut result);
                 }
             }
-            None
+            result.into_iter().map(|(x, y, w, h)| (x, y + h - height, w, height)).collect()
+        })
+    }
+
+    pub(crate) fn layout_extra_carets(
+        &mut self,
+        entity: Entity,
+        position: (f32, f32),
+        justify: (f32, f32),
+        width: f32,
+    ) -> Vec<(f32, f32, f32, f32)> {
+        if !self.has_buffer(entity) {
+            return vec![];
+        }
+
+        let cursors = self.extra_cursors(entity);
+        let spacing = self.spacing(entity);
+        self.with_buffer(entity, |buffer| {
+            cursors
+                .into_iter()
+                .filter_map(|(_, cursor)| {
+                    let cursor = offset_to_cursor(buffer, cursor);
+                    layout_cursor(buffer, cursor, position, justify, spacing, width)
+                })
+                .collect()
+        })
+    }
+
+    pub(crate) fn layout_drop_caret(
+        &mut self,
+        entity: Entity,
+        position: (f32, f32),
+        justify: (f32, f32),
+        width: f32,
+    ) -> Option<(f32, f32, f32, f32)> {
+        self.with_int_mut(move |int: &mut TextContextInternal| {
+            let offset = *int.drop_carets.get(&entity)?;
+            let buffer = int.buffers.get(&entity)?.buffer();
+            let cursor = offset_to_cursor(buffer, offset);
+            let spacing = int.spacings.get(&entity).copied().unwrap_or_default();
+            layout_cursor(buffer, cursor, position, justify, spacing, width)
         })
     }
 
@@ -357,6 +758,278 @@ impl TextContext {
     }
 }
 
+/// Lays out a caret of the given width at the position of `cursor`.
+fn layout_cursor(
+    buffer: &Buffer,
+    cursor: Cursor,
+    position: (f32, f32),
+    justify: (f32, f32),
+    spacing: Spacing,
+    width: f32,
+) -> Option<(f32, f32, f32, f32)> {
+    let total_height = buffer.layout_runs().len() as i32 * buffer.metrics().line_height;
+    for run in buffer.layout_runs() {
+        if let Some((x, _)) = run.highlight(cursor, cursor) {
+            let spaces = widened_spaces(buffer, &run, spacing);
+            let x = widen_x(&spaces, x);
+            let y = run.line_y as f32 - buffer.metrics().font_size as f32;
+            let x = x + position.0 - widened_width(&run, &spaces) * justify.0;
+            let y = y + position.1 - total_height as f32 * justify.1;
+            return Some((x - width / 2.0, y, width, buffer.metrics().line_height as f32));
+        }
+    }
+    None
+}
+
+/// Lays out a block caret covering the grapheme cluster after `cursor`. At the end of a line the
+/// block is half the font size wide.
+fn layout_block_cursor(
+    buffer: &Buffer,
+    cursor: Cursor,
+    position: (f32, f32),
+    justify: (f32, f32),
+    spacing: Spacing,
+) -> Option<(f32, f32, f32, f32)> {
+    let text = buffer.lines.get(cursor.line)?.text();
+    let end =
+        text[cursor.index..].graphemes(true).next().map(|grapheme| cursor.index + grapheme.len());
+    let font_size = buffer.metrics().font_size as f32;
+    let total_height = buffer.layout_runs().len() as i32 * buffer.metrics().line_height;
+    for run in buffer.layout_runs() {
+        let highlight = match end {
+            Some(end) => run.highlight(cursor, Cursor::new(cursor.line, end)),
+            None => run.highlight(cursor, cursor).map(|(x, _)| (x, font_size / 2.0)),
+        };
+        if let Some((x, w)) = highlight {
+            let spaces = widened_spaces(buffer, &run, spacing);
+            let (x, w) = widen_span(&spaces, x, w);
+            let y = run.line_y as f32 - font_size;
+            let x = x + position.0 - widened_width(&run, &spaces) * justify.0;
+            let y = y + position.1 - total_height as f32 * justify.1;
+            return Some((x, y, w, buffer.metrics().line_height as f32));
+        }
+    }
+    None
+}
+
+/// Converts a cursor into a byte offset into the text of the buffer, with lines joined by `\n`.
+pub(crate) fn cursor_to_offset(buffer: &Buffer, cursor: Cursor) -> usize {
+    buffer.lines.iter().take(cursor.line).map(|line| line.text().len() + 1).sum::<usize>()
+        + cursor.index
+}
+
+/// Converts a byte offset into the text of the buffer, with lines joined by `\n`, into a cursor.
+/// Offsets past the end of the text are clamped to the end of the last line.
+pub(crate) fn offset_to_cursor(buffer: &Buffer, offset: usize) -> Cursor {
+    let mut line_start = 0;
+    for (line_i, line) in buffer.lines.iter().enumerate() {
+        let line_end = line_start + line.text().len();
+        if offset <= line_end || line_i + 1 == buffer.lines.len() {
+            return Cursor::new(line_i, offset.saturating_sub(line_start).min(line.text().len()));
+        }
+        line_start = line_end + 1;
+    }
+
+    Cursor::new(0, 0)
+}
+
+/// Moves the cursor of an editor to the given position. Cosmic has no way of setting the cursor
+/// directly, so this walks there from the start of the buffer. The selection is left untouched.
+pub(crate) fn set_editor_cursor(editor: &mut Editor, cursor: Cursor) {
+    editor.action(Action::BufferStart);
+    while editor.cursor().line < cursor.line {
+        let line = editor.cursor().line;
+        editor.action(Action::ParagraphEnd);
+        editor.action(Action::Next);
+        if editor.cursor().line == line {
+            // Already on the last line.
+            break;
+        }
+    }
+
+    let line_len = editor.buffer().lines[editor.cursor().line].text().len();
+    let index = cursor.index.min(line_len);
+    while editor.cursor().index < index {
+        editor.action(Action::Next);
+    }
+}
+
+/// Pushes the rectangles covering the text between two cursors.
+fn layout_highlight(
+    buffer: &Buffer,
+    start: Cursor,
+    end: Cursor,
+    position: (f32, f32),
+    justify: (f32, f32),
+    spacing: Spacing,
+    result: &mut Vec<(f32, f32, f32, f32)>,
+) {
+    let total_height = buffer.layout_runs().len() as i32 * buffer.metrics().line_height;
+    for run in buffer.layout_runs() {
+        if let Some((x, w)) = run.highlight(start, end) {
+            let spaces = widened_spaces(buffer, &run, spacing);
+            let (x, w) = widen_span(&spaces, x, w);
+            let y = run.line_y as f32 - buffer.metrics().font_size as f32;
+            let x = x + position.0 - widened_width(&run, &spaces) * justify.0;
+            let y = y + position.1 - total_height as f32 * justify.1;
+            result.push((x, y, w, buffer.metrics().line_height as f32));
+        }
+    }
+}
+
+/// How the whitespace of the lines of a text is widened when it is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Spacing {
+    /// Whether wrapped lines are stretched to the width of the buffer by widening the spaces
+    /// between words.
+    pub justified: bool,
+    /// The distance between tab stops in ems, to which tab characters advance.
+    pub tab_stop: Option<f32>,
+}
+
+/// Returns the whitespace glyphs of a run which are widened when it is drawn, as their positions
+/// and widths within the run and the width added to each, in order. Tab characters advance to the
+/// next tab stop, except in right-to-left runs, and if the text is justified the spaces between
+/// words are widened to stretch the run to the width of the buffer. The last line of a paragraph
+/// isn't stretched. Glyphs without a width are left alone.
+pub(crate) fn widened_spaces(
+    buffer: &Buffer,
+    run: &LayoutRun,
+    spacing: Spacing,
+) -> Vec<(f32, f32, f32)> {
+    let is_tab = |glyph: &&LayoutGlyph| &run.text[glyph.start..glyph.end] == "\t";
+    let tab_width = spacing.tab_stop.map(|tab_stop| tab_stop * buffer.metrics().font_size as f32);
+    let tab_width = tab_width.filter(|tab_width| *tab_width > 0.0 && !run.rtl);
+
+    let mut spaces = match tab_width {
+        Some(tab_width) => {
+            let tabs = run.glyphs.iter().filter(|glyph| is_tab(glyph) && glyph.w > 0.0);
+            advance_tabs(tabs.map(|glyph| (glyph.x, glyph.w)).collect(), tab_width)
+        }
+        None => Vec::new(),
+    };
+
+    if spacing.justified && is_wrapped(run) {
+        let is_space = |glyph: &&LayoutGlyph| {
+            run.text[glyph.start..glyph.end].chars().all(char::is_whitespace)
+                && !(tab_width.is_some() && is_tab(glyph))
+        };
+        let words = run.glyphs.iter().filter(|glyph| !is_space(glyph));
+        let (left, right) = words
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(left, right), glyph| {
+                (left.min(glyph.x), right.max(glyph.x + glyph.w))
+            });
+        // Spaces at the ends of the line, such as the one it was wrapped at, aren't widened.
+        let between = run
+            .glyphs
+            .iter()
+            .filter(|glyph| is_space(glyph) && glyph.w > 0.0 && glyph.x > left && glyph.x < right)
+            .map(|glyph| (glyph.x, glyph.w))
+            .collect::<Vec<_>>();
+        if !between.is_empty() {
+            let right = widen_x(&spaces, right);
+            let extra = ((buffer.size().0 as f32 - right) / between.len() as f32).max(0.0);
+            spaces.extend(between.into_iter().map(|(x, w)| (x, w, extra)));
+            spaces.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+    }
+
+    spaces
+}
+
+/// Returns the width added to each of the given tabs, as positions and widths within a run, for it
+/// to reach the next multiple of `tab_width` once the tabs before it have been widened.
+fn advance_tabs(mut tabs: Vec<(f32, f32)>, tab_width: f32) -> Vec<(f32, f32, f32)> {
+    tabs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut shift = 0.0;
+    tabs.into_iter()
+        .map(|(x, w)| {
+            let drawn_x = x + shift;
+            let stop = ((drawn_x / tab_width).floor() + 1.0) * tab_width;
+            let extra = (stop - drawn_x - w).max(0.0);
+            shift += extra;
+            (x, w, extra)
+        })
+        .collect()
+}
+
+/// Returns whether a run is followed by more of its line, so isn't the last line of a paragraph.
+fn is_wrapped(run: &LayoutRun) -> bool {
+    run.glyphs.iter().map(|glyph| glyph.end).max().map_or(false, |end| end < run.text.len())
+}
+
+/// Returns the width of a run once the given spaces are widened.
+pub(crate) fn widened_width(run: &LayoutRun, spaces: &[(f32, f32, f32)]) -> f32 {
+    run.line_w + spaces.iter().map(|(_, _, extra)| extra).sum::<f32>()
+}
+
+/// Moves an x position within a run to where it is drawn once the given spaces are widened.
+pub(crate) fn widen_x(spaces: &[(f32, f32, f32)], x: f32) -> f32 {
+    spaces.iter().fold(x, |widened, (space_x, space_w, extra)| {
+        widened + extra * ((x - space_x) / space_w).clamp(0.0, 1.0)
+    })
+}
+
+/// Moves an x position at which a run is drawn with the given spaces widened back to the position
+/// within the run, as needed to hit-test it.
+pub(crate) fn unwiden_x(spaces: &[(f32, f32, f32)], x: f32) -> f32 {
+    let mut shift = 0.0;
+    for (space_x, space_w, extra) in spaces {
+        let start = space_x + shift;
+        if x < start {
+            break;
+        }
+        if x < start + space_w + extra {
+            return space_x + (x - start) * space_w / (space_w + extra);
+        }
+        shift += extra;
+    }
+    x - shift
+}
+
+/// Moves a span within a run to where it is drawn once the given spaces are widened.
+pub(crate) fn widen_span(spaces: &[(f32, f32, f32)], x: f32, w: f32) -> (f32, f32) {
+    let left = widen_x(spaces, x);
+    (left, widen_x(spaces, x + w) - left)
+}
+
+/// Applies the attributes of a span on top of the default attributes of an entity, keeping the
+/// defaults for anything the span leaves unset.
+fn merge_attrs<'a>(defaults: Attrs<'a>, span: Attrs<'a>) -> Attrs<'a> {
+    let mut attrs = defaults;
+    if let Some(color) = span.color_opt {
+        attrs = attrs.color(color);
+    }
+    if span.family != Family::SansSerif {
+        attrs = attrs.family(span.family).monospaced(span.monospaced);
+    }
+    if span.weight != Weight::NORMAL {
+        attrs = attrs.weight(span.weight);
+    }
+    if span.style != FontStyle::Normal {
+        attrs = attrs.style(span.style);
+    }
+    attrs.metadata(span.metadata)
+}
+
+/// Maps a range through an edit which replaced `removed` with `inserted` bytes. Text inserted at the
+/// start of the range is placed before it, while text inserted at the end extends it.
+fn remap_range(range: Range<usize>, removed: Range<usize>, inserted: usize) -> Range<usize> {
+    remap_offset(range.start, removed.clone(), inserted)..remap_offset(range.end, removed, inserted)
+}
+
+/// Maps a byte offset through an edit which replaced `removed` with `inserted` bytes. Offsets inside
+/// the removed text end up after the inserted text.
+pub(crate) fn remap_offset(pos: usize, removed: Range<usize>, inserted: usize) -> usize {
+    if pos < removed.start {
+        pos
+    } else if pos >= removed.end {
+        pos - (removed.end - removed.start) + inserted
+    } else {
+        removed.start + inserted
+    }
+}
+
 impl TextContext {
     pub fn new_from_locale_and_db(locale: String, font_db: Database) -> Self {
         TextContextBuilder {
@@ -367,6 +1040,17 @@ impl TextContext {
                 rendered_glyphs: FnvHashMap::default(),
                 glyph_textures: vec![],
                 buffers: HashMap::new(),
+                spans: HashMap::new(),
+                match_highlights: HashMap::new(),
+                drop_carets: HashMap::new(),
+                block_carets: HashSet::new(),
+                spacings: HashMap::new(),
+                extra_cursors: HashMap::new(),
+                preedits: HashMap::new(),
+                misspellings: HashMap::new(),
+                edited_ranges: HashMap::new(),
+                synced_attrs: HashMap::new(),
+                average_advances: HashMap::new(),
             },
         }
         .build()
@@ -395,3 +1079,78 @@ pub struct RenderedGlyph {
 //    Fill,
 //    Stroke(u32),
 //}
+
+#[cfg(test)]
+mod tests {
+    use super::{advance_tabs, remap_range, unwiden_x, widen_x, Attrs};
+    use crate::prelude::*;
+
+    #[test]
+    fn remap_range_insertion() {
+        // Before, inside, at the start and at the end of the range.
+        assert_eq!(remap_range(5..10, 2..2, 3), 8..13);
+        assert_eq!(remap_range(5..10, 7..7, 3), 5..13);
+        assert_eq!(remap_range(5..10, 5..5, 3), 8..13);
+        assert_eq!(remap_range(5..10, 10..10, 3), 5..13);
+        assert_eq!(remap_range(5..10, 12..12, 3), 5..10);
+    }
+
+    #[test]
+    fn remap_range_deletion() {
+        assert_eq!(remap_range(5..10, 0..2, 0), 3..8);
+        assert_eq!(remap_range(5..10, 6..8, 0), 5..8);
+        assert_eq!(remap_range(5..10, 3..7, 0), 3..6);
+        assert_eq!(remap_range(5..10, 4..12, 0), 4..4);
+    }
+
+    #[test]
+    fn remap_range_replacement() {
+        assert_eq!(remap_range(5..10, 6..8, 4), 5..12);
+        assert_eq!(remap_range(5..10, 0..6, 1), 1..5);
+    }
+
+    #[test]
+    fn cleared_buffer_leaves_nothing_behind() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+
+        cx.text_context.with_buffer(entity, |buf| buf.set_text("hello", Attrs::new()));
+        cx.text_context.set_extra_cursors(entity, vec![(1, 1)]);
+        cx.text_context.set_preedit(entity, Some(0..2));
+        cx.text_context.set_misspellings(entity, vec![0..5]);
+        cx.text_context.clear_buffer(entity);
+
+        let origin = (0.0, 0.0);
+        assert!(cx.text_context.extra_cursors(entity).is_empty());
+        assert!(cx.text_context.layout_preedit(entity, origin, origin, 1.0).is_empty());
+        assert!(cx.text_context.layout_misspellings(entity, origin, origin, 1.0).is_empty());
+        assert!(cx.text_context.layout_extra_carets(entity, origin, origin, 1.0).is_empty());
+        assert!(!cx.text_context.has_buffer(entity));
+    }
+
+    #[test]
+    fn justified_positions() {
+        // Two spaces, each widened by 4.
+        let spaces = [(10.0, 2.0, 4.0), (20.0, 2.0, 4.0)];
+        assert_eq!(widen_x(&spaces, 5.0), 5.0);
+        assert_eq!(widen_x(&spaces, 11.0), 13.0);
+        assert_eq!(widen_x(&spaces, 15.0), 19.0);
+        assert_eq!(widen_x(&spaces, 25.0), 33.0);
+
+        for x in [5.0, 11.0, 15.0, 25.0] {
+            assert_eq!(unwiden_x(&spaces, widen_x(&spaces, x)), x);
+        }
+    }
+
+    #[test]
+    fn tab_positions() {
+        // Tabs 2 wide at 3 and 9 reach the stops at 8 and 16, once the first has moved the second
+        // to 12. A tab which is wider than the space to the next stop isn't narrowed.
+        let spaces = advance_tabs(vec![(9.0, 2.0), (3.0, 2.0), (17.0, 3.0)], 8.0);
+        assert_eq!(spaces, vec![(3.0, 2.0, 3.0), (9.0, 2.0, 2.0), (17.0, 3.0, 0.0)]);
+        assert_eq!(widen_x(&spaces, 5.0), 8.0);
+        assert_eq!(widen_x(&spaces, 11.0), 16.0);
+        assert_eq!(unwiden_x(&spaces, 16.0), 11.0);
+        assert_eq!(unwiden_x(&spaces, 14.0), 10.0);
+    }
+}
@@ -4,5 +4,8 @@ pub use movement::*;
 pub mod scrolling;
 pub use scrolling::*;
 
+mod search;
+pub use search::*;
+
 pub(crate) mod cosmic;
 pub(crate) use cosmic::*;
@@ -13,7 +13,13 @@ pub enum Movement {
     Line(Direction),
     Page(Direction),
     Body(Direction),
+    /// Moves to the start of the paragraph, or of the previous or next paragraph if already there,
+    /// where paragraphs are separated by line breaks rather than wrapping.
+    Paragraph(Direction),
     LineStart,
+    /// Moves to the first non-whitespace character of the line, or to the start of the line if
+    /// the cursor is already there.
+    SmartHome,
     LineEnd,
     Vertical(VerticalMovement),
 }
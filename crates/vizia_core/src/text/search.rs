@@ -0,0 +1,111 @@
+#[cfg(feature = "regex")]
+use regex::Regex;
+#[cfg(feature = "regex")]
+use std::ops::Range;
+
+/// Finds all non-overlapping occurrences of `needle` in `haystack`, returning the start and end
+/// byte offsets of each match.
+pub fn find_matches(haystack: &str, needle: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+    let mut result = vec![];
+    if needle.is_empty() {
+        return result;
+    }
+
+    if case_sensitive {
+        result.extend(haystack.match_indices(needle).map(|(start, m)| (start, start + m.len())));
+        return result;
+    }
+
+    // Lowercasing can change the byte length of the text, so compare character by character to
+    // keep the offsets into the original haystack.
+    let mut search_from = 0;
+    for (start, _) in haystack.char_indices() {
+        if start < search_from {
+            continue;
+        }
+
+        let mut hay_chars = haystack[start..].char_indices();
+        let mut end = start;
+        let matched = needle.chars().all(|n| match hay_chars.next() {
+            Some((offset, h)) if h.to_lowercase().eq(n.to_lowercase()) => {
+                end = start + offset + h.len_utf8();
+                true
+            }
+            _ => false,
+        });
+
+        if matched {
+            result.push((start, end));
+            search_from = end;
+        }
+    }
+
+    result
+}
+
+/// Returns the start and end byte offsets of every match of `pattern` in `haystack`, each followed
+/// by those of its capture groups, or `None` for groups which didn't take part in the match.
+#[cfg(feature = "regex")]
+pub fn find_regex_matches(haystack: &str, pattern: &Regex) -> Vec<Vec<Option<(usize, usize)>>> {
+    pattern
+        .captures_iter(haystack)
+        .map(|captures| captures.iter().map(|group| group.map(|m| (m.start(), m.end()))).collect())
+        .collect()
+}
+
+/// Returns the byte range of every match of `pattern` in `haystack` along with its replacement,
+/// in which references such as `$1` or `${name}` are replaced by the text of the capture groups.
+#[cfg(feature = "regex")]
+pub fn regex_replacements(
+    haystack: &str,
+    pattern: &Regex,
+    replacement: &str,
+) -> Vec<(Range<usize>, String)> {
+    pattern
+        .captures_iter(haystack)
+        .map(|captures| {
+            let whole = captures.get(0).unwrap();
+            let mut text = String::new();
+            captures.expand(replacement, &mut text);
+            (whole.start()..whole.end(), text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_matches;
+    #[cfg(feature = "regex")]
+    use super::{find_regex_matches, regex_replacements, Regex};
+
+    #[test]
+    fn case_sensitive() {
+        assert_eq!(find_matches("abc Abc abc", "abc", true), vec![(0, 3), (8, 11)]);
+        assert_eq!(find_matches("aaaa", "aa", true), vec![(0, 2), (2, 4)]);
+        assert_eq!(find_matches("abc", "", true), vec![]);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(find_matches("abc Abc ABC", "abc", false), vec![(0, 3), (4, 7), (8, 11)]);
+        assert_eq!(find_matches("Straße STRASSE", "straße", false), vec![(0, 7)]);
+        assert_eq!(find_matches("ÄÖ äö", "äö", false), vec![(0, 4), (5, 9)]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex() {
+        let pattern = Regex::new(r"(\w+)=(\d+)?").unwrap();
+        assert_eq!(
+            find_regex_matches("a=1 b=", &pattern),
+            vec![
+                vec![Some((0, 3)), Some((0, 1)), Some((2, 3))],
+                vec![Some((4, 6)), Some((4, 5)), None]
+            ]
+        );
+        assert_eq!(
+            regex_replacements("a=1 b=", &pattern, "$2:$1"),
+            vec![(0..3, "1:a".to_owned()), (4..6, ":b".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use std::any::TypeId;
+use std::sync::Arc;
+
+use crate::prelude::*;
+use crate::views::textbox::TextboxData;
+
+/// The state of a [`ComboBox`], which is the text its options are filtered by and which of them is
+/// highlighted while the list of options is open.
+#[derive(Lens)]
+pub struct ComboBoxData {
+    is_open: bool,
+    filter: String,
+    highlighted: usize,
+}
+
+impl Model for ComboBoxData {
+    fn event(&mut self, _: &mut EventContext, event: &mut Event) {
+        event.map(|combobox_event, _| match combobox_event {
+            ComboBoxEvent::SetFilter(filter) => {
+                self.filter = filter.clone();
+                self.highlighted = 0;
+                self.is_open = true;
+            }
+
+            ComboBoxEvent::SetHighlighted(index) => {
+                self.highlighted = *index;
+            }
+
+            ComboBoxEvent::Open => {
+                self.is_open = true;
+            }
+
+            ComboBoxEvent::Close => {
+                self.is_open = false;
+            }
+
+            ComboBoxEvent::Select(_) => {
+                self.is_open = false;
+            }
+        });
+    }
+}
+
+/// Events which change the state of a [`ComboBox`].
+#[derive(Debug)]
+pub enum ComboBoxEvent {
+    /// Shows the options which contain the given text, ignoring case, and opens the list.
+    SetFilter(String),
+    /// Highlights the option at the given index among those shown.
+    SetHighlighted(usize),
+    Open,
+    Close,
+    /// Sets the text to the given option and submits it, closing the list.
+    Select(String),
+}
+
+/// A textbox with a list of options below it, which is narrowed down to those containing the typed
+/// text. An option is picked by clicking it, or by highlighting it with the Up and Down keys while
+/// the list is open and pressing Enter. Picking an option sets the text and submits it.
+///
+/// # Example
+///
+/// ```
+/// # use vizia_core::prelude::*;
+/// #
+/// # #[derive(Lens)]
+/// # struct AppData {
+/// #     fruit: String,
+/// #     fruits: Vec<String>,
+/// # }
+/// #
+/// # impl Model for AppData {}
+/// #
+/// # enum AppEvent {
+/// #     SetFruit(String),
+/// # }
+/// #
+/// # let cx = &mut Context::default();
+/// #
+/// # AppData { fruit: String::new(), fruits: vec!["Apple".to_owned(), "Banana".to_owned()] }
+/// #     .build(cx);
+/// #
+/// ComboBox::new(cx, AppData::fruit, AppData::fruits)
+///     .on_submit(|cx, text| cx.emit(AppEvent::SetFruit(text)))
+///     .width(Pixels(150.0));
+/// ```
+pub struct ComboBox<O> {
+    options: O,
+    textbox: Entity,
+    /// The key bindings of the textbox from before the list was opened, which are restored when it
+    /// closes.
+    key_bindings: Option<KeyBindings>,
+}
+
+impl<O> ComboBox<O>
+where
+    O: Lens<Target = Vec<String>>,
+{
+    /// Creates a combo box whose text is bound to `lens` and which offers the options bound to
+    /// `options`.
+    pub fn new<L>(cx: &mut Context, lens: L, options: O) -> Handle<Self>
+    where
+        L: Lens,
+        <L as Lens>::Target: Data + Clone + ToString,
+    {
+        let mut textbox = Entity::null();
+        let list_options = options.clone();
+        let combobox = Self { options, textbox: Entity::null(), key_bindings: None };
+        let handle = combobox.build(cx, |cx| {
+            ComboBoxData { is_open: false, filter: String::new(), highlighted: 0 }.build(cx);
+
+            textbox = Textbox::new(cx, lens)
+                .on_edit(|cx, text| cx.emit(ComboBoxEvent::SetFilter(text)))
+                .width(Stretch(1.0))
+                .entity();
+
+            let popup = Popup::new(cx, ComboBoxData::is_open, false, move |cx| {
+                let list_options = list_options.clone();
+                Binding::new(cx, ComboBoxData::filter, move |cx, filter| {
+                    let filter = filter.get(cx);
+                    Binding::new(cx, list_options.clone(), move |cx, options| {
+                        let options = filter_options(&options.get(cx), &filter);
+                        for (index, option) in options.into_iter().enumerate() {
+                            Label::new(cx, &option)
+                                .class("combobox_option")
+                                .role(Role::ListItem)
+                                .checked(ComboBoxData::highlighted.map(move |h| *h == index))
+                                .on_press(move |cx| cx.emit(ComboBoxEvent::Select(option.clone())));
+                        }
+                    });
+                });
+            })
+            .on_blur(|cx| cx.emit(ComboBoxEvent::Close))
+            .top(Percentage(100.0))
+            .width(Stretch(1.0))
+            .height(Auto)
+            .entity();
+
+            // Pressing an option mustn't submit the typed text before the option is picked.
+            if let Some(textbox) =
+                cx.views.get_mut(&textbox).and_then(|view| view.downcast_mut::<Textbox<L>>())
+            {
+                textbox.popup = Some(popup);
+            }
+        });
+
+        handle.modify(|combobox| combobox.textbox = textbox).role(Role::ComboBox)
+    }
+
+    /// Returns the options which are shown for the current filter.
+    fn shown_options(&self, cx: &mut EventContext) -> Vec<String> {
+        let filter = cx.data::<ComboBoxData>().map(|data| data.filter.clone()).unwrap_or_default();
+        filter_options(&self.options.get(cx), &filter)
+    }
+
+    /// Returns the current key bindings of the textbox.
+    fn textbox_key_bindings(&self, cx: &EventContext) -> KeyBindings {
+        cx.data
+            .get(self.textbox)
+            .and_then(|model_data_store| model_data_store.models.get(&TypeId::of::<TextboxData>()))
+            .and_then(|model| model.downcast_ref::<TextboxData>())
+            .map(|text_data| text_data.key_bindings().clone())
+            .unwrap_or_else(|| KeyBindings::new(false))
+    }
+
+    /// Gives the textbox back the key bindings it had before the list was opened.
+    fn restore_key_bindings(&mut self, cx: &mut EventContext) {
+        if let Some(key_bindings) = self.key_bindings.take() {
+            cx.emit_to(self.textbox, TextEvent::SetKeyBindings(key_bindings));
+        }
+    }
+}
+
+/// Returns the options which contain `filter`, ignoring case.
+fn filter_options(options: &[String], filter: &str) -> Vec<String> {
+    let filter = filter.to_lowercase();
+    options.iter().filter(|option| option.to_lowercase().contains(&filter)).cloned().collect()
+}
+
+/// The key bindings of the textbox while the list is open, which are `key_bindings` without the
+/// keys that navigate the list, leaving those to the combo box.
+fn open_key_bindings(key_bindings: KeyBindings) -> KeyBindings {
+    [Code::ArrowUp, Code::ArrowDown, Code::Enter, Code::Escape]
+        .into_iter()
+        .fold(key_bindings, |key_bindings, code| {
+            key_bindings.unbind(KeyChord::new(Modifiers::empty(), code))
+        })
+}
+
+impl<O> View for ComboBox<O>
+where
+    O: Lens<Target = Vec<String>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("combobox")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|combobox_event, _| match combobox_event {
+            // The arrow keys move the caret again once the list is closed.
+            ComboBoxEvent::SetFilter(_) | ComboBoxEvent::Open => {
+                if self.key_bindings.is_none() {
+                    let key_bindings = self.textbox_key_bindings(cx);
+                    self.key_bindings = Some(key_bindings.clone());
+                    cx.emit_to(
+                        self.textbox,
+                        TextEvent::SetKeyBindings(open_key_bindings(key_bindings)),
+                    );
+                }
+            }
+
+            ComboBoxEvent::Close => self.restore_key_bindings(cx),
+
+            // The text is replaced once editing has ended so that `on_edit` doesn't filter the
+            // options by it and open the list again.
+            ComboBoxEvent::Select(option) => {
+                self.restore_key_bindings(cx);
+                cx.emit_to(self.textbox, TextEvent::EndEdit);
+                cx.emit_to(self.textbox, TextEvent::ResetText(option.clone()));
+                cx.emit_to(self.textbox, TextEvent::Submit(SubmitReason::Programmatic));
+            }
+
+            ComboBoxEvent::SetHighlighted(_) => {}
+        });
+
+        event.map(|window_event, _| match window_event {
+            WindowEvent::KeyDown(code, _) => {
+                let (is_open, highlighted) = match cx.data::<ComboBoxData>() {
+                    Some(data) => (data.is_open, data.highlighted),
+                    None => return,
+                };
+                if !is_open {
+                    return;
+                }
+
+                let options = self.shown_options(cx);
+                match code {
+                    Code::ArrowDown if !options.is_empty() => {
+                        cx.emit(ComboBoxEvent::SetHighlighted((highlighted + 1) % options.len()));
+                    }
+
+                    Code::ArrowUp if !options.is_empty() => {
+                        let index = (highlighted + options.len() - 1) % options.len();
+                        cx.emit(ComboBoxEvent::SetHighlighted(index));
+                    }
+
+                    Code::Enter => match options.get(highlighted) {
+                        Some(option) => cx.emit(ComboBoxEvent::Select(option.clone())),
+                        None => cx.emit(ComboBoxEvent::Close),
+                    },
+
+                    Code::Escape => cx.emit(ComboBoxEvent::Close),
+
+                    _ => {}
+                }
+            }
+
+            _ => {}
+        });
+    }
+}
+
+impl<'a, O> Handle<'a, ComboBox<O>>
+where
+    O: Lens<Target = Vec<String>>,
+{
+    /// Sets a callback which is called with the text when it is submitted, either from the textbox
+    /// or by picking an option.
+    pub fn on_submit<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, String) + Send + Sync,
+    {
+        let textbox = self
+            .cx
+            .views
+            .get(&self.entity)
+            .and_then(|view| view.downcast_ref::<ComboBox<O>>())
+            .map(|combobox| combobox.textbox);
+        if let Some(textbox) = textbox {
+            self.cx.emit_to(
+                textbox,
+                TextEvent::SetOnSubmit(Some(Arc::new(move |cx, text, _| (callback)(cx, text)))),
+            );
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::backend::BackendContext;
+    use crate::events::EventManager;
+    use std::sync::Mutex;
+
+    #[derive(Lens)]
+    struct TestData {
+        fruit: String,
+        fruits: Vec<String>,
+    }
+
+    impl Model for TestData {}
+
+    /// Builds a combo box of fruits whose submitted texts are logged, and returns its textbox.
+    fn build_combobox(cx: &mut Context, log: Arc<Mutex<Vec<String>>>) -> Entity {
+        let fruits = ["Apple", "Banana", "Mango"].map(str::to_owned).to_vec();
+        TestData { fruit: String::new(), fruits }.build(cx);
+
+        let mut textbox = Entity::null();
+        ComboBox::new(cx, TestData::fruit, TestData::fruits)
+            .on_submit(move |_, text| log.lock().unwrap().push(text))
+            .modify(|combobox| textbox = combobox.textbox);
+        textbox
+    }
+
+    fn key_bindings(cx: &Context, textbox: Entity) -> KeyBindings {
+        cx.data
+            .get(textbox)
+            .and_then(|model_data_store| model_data_store.models.get(&TypeId::of::<TextboxData>()))
+            .and_then(|model| model.downcast_ref::<TextboxData>())
+            .map(|text_data| text_data.key_bindings().clone())
+            .unwrap()
+    }
+
+    fn key_down(cx: &mut Context, textbox: Entity, code: Code) {
+        cx.emit_custom(
+            Event::new(WindowEvent::KeyDown(code, None)).origin(Entity::root()).target(textbox),
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let options = ["Apple", "Banana", "Mango"].map(str::to_owned);
+        assert_eq!(filter_options(&options, "AN"), ["Banana", "Mango"]);
+        assert_eq!(filter_options(&options, "pp"), ["Apple"]);
+        assert_eq!(filter_options(&options, ""), options);
+        assert!(filter_options(&options, "kiwi").is_empty());
+    }
+
+    #[test]
+    fn pick_option_with_keys() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let textbox = build_combobox(&mut cx, log.clone());
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        let ctrl_k = KeyChord::new(Modifiers::CTRL, Code::KeyK);
+        let arrow_down = KeyChord::new(Modifiers::empty(), Code::ArrowDown);
+        let custom = KeyBindings::new(false).bind(ctrl_k, TextEvent::Clear);
+        cx.emit_to(textbox, TextEvent::SetKeyBindings(custom));
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("an".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
+
+        // The list takes the arrow keys while it is open.
+        assert!(key_bindings(&cx, textbox).get(&arrow_down).is_none());
+        assert!(key_bindings(&cx, textbox).get(&ctrl_k).is_some());
+
+        // Banana is highlighted first.
+        for code in [Code::ArrowDown, Code::Enter] {
+            key_down(&mut cx, textbox, code);
+            while event_manager.flush_events(&mut cx) {}
+        }
+
+        assert_eq!(*log.lock().unwrap(), ["Mango"]);
+        assert!(key_bindings(&cx, textbox).get(&arrow_down).is_some());
+        assert!(key_bindings(&cx, textbox).get(&ctrl_k).is_some());
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("p".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
+        for code in [Code::ArrowUp, Code::Escape] {
+            key_down(&mut cx, textbox, code);
+            while event_manager.flush_events(&mut cx) {}
+        }
+
+        assert_eq!(*log.lock().unwrap(), ["Mango"]);
+        assert!(key_bindings(&cx, textbox).get(&arrow_down).is_some());
+        assert!(key_bindings(&cx, textbox).get(&ctrl_k).is_some());
+    }
+
+    #[test]
+    fn pick_option_with_mouse() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let textbox = build_combobox(&mut cx, log.clone());
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("an".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_data_updates();
+        while event_manager.flush_events(&mut cx) {}
+
+        let options: Vec<_> = cx
+            .tree
+            .into_iter()
+            .filter(|entity| {
+                cx.style
+                    .classes
+                    .get(*entity)
+                    .map_or(false, |classes| classes.contains("combobox_option"))
+            })
+            .collect();
+        assert_eq!(options.len(), 2);
+
+        // The textbox captures the mouse while editing, so the click arrives there first.
+        cx.hovered = options[1];
+        for event in
+            [WindowEvent::MouseDown(MouseButton::Left), WindowEvent::MouseUp(MouseButton::Left)]
+        {
+            cx.emit_custom(Event::new(event).origin(Entity::root()).target(Entity::root()));
+            while event_manager.flush_events(&mut cx) {}
+        }
+
+        assert_eq!(*log.lock().unwrap(), ["Mango"]);
+    }
+}
@@ -4,6 +4,7 @@
 
 mod button;
 mod checkbox;
+mod combobox;
 mod dropdown;
 mod element;
 mod image;
@@ -24,6 +25,7 @@ mod textbox;
 pub use self::image::Image;
 pub use button::Button;
 pub use checkbox::Checkbox;
+pub use combobox::{ComboBox, ComboBoxData, ComboBoxEvent};
 pub use dropdown::Dropdown;
 pub use element::Element;
 pub use knob::{ArcTrack, Knob, KnobMode, TickKnob, Ticks};
@@ -37,7 +39,10 @@ pub use scrollview::{ScrollData, ScrollEvent, ScrollView};
 pub use slider::Slider;
 pub use stack::{HStack, VStack, ZStack};
 pub use table::{Table, TableColumn};
-pub use textbox::{TextEvent, Textbox};
+pub use textbox::{
+    BaseDirection, CaseTransform, ClipboardData, EditDelta, KeyBindings, LineEnding, SubmitReason,
+    TextAlign, TextEvent, Textbox,
+};
 
 use crate::prelude::*;
 
@@ -64,6 +64,7 @@ impl Model for ScrollData {
 
 pub struct ScrollView<L> {
     data: L,
+    scroll_sensitivity: f32,
 }
 
 impl ScrollView<Wrapper<scroll_data_derived_lenses::root>> {
@@ -78,19 +79,22 @@ impl ScrollView<Wrapper<scroll_data_derived_lenses::root>> {
     where
         F: 'static + FnOnce(&mut Context),
     {
-        Self { data: ScrollData::root }.build(cx, move |cx| {
-            ScrollData {
-                scroll_x: initial_x,
-                scroll_y: initial_y,
-                child_x: 0.0,
-                child_y: 0.0,
-                parent_x: 0.0,
-                parent_y: 0.0,
-            }
-            .build(cx);
+        Self { data: ScrollData::root, scroll_sensitivity: SCROLL_SENSITIVITY }.build(
+            cx,
+            move |cx| {
+                ScrollData {
+                    scroll_x: initial_x,
+                    scroll_y: initial_y,
+                    child_x: 0.0,
+                    child_y: 0.0,
+                    parent_x: 0.0,
+                    parent_y: 0.0,
+                }
+                .build(cx);
 
-            Self::common_builder(cx, ScrollData::root, content, scroll_x, scroll_y);
-        })
+                Self::common_builder(cx, ScrollData::root, content, scroll_x, scroll_y);
+            },
+        )
     }
 }
 
@@ -109,7 +113,7 @@ impl<L: Lens<Target = ScrollData>> ScrollView<L> {
             panic!("ScrollView::custom requires a ScrollData to be built into a parent");
         }
 
-        Self { data: data.clone() }.build(cx, |cx| {
+        Self { data: data.clone(), scroll_sensitivity: SCROLL_SENSITIVITY }.build(cx, |cx| {
             Self::common_builder(cx, data, content, scroll_x, scroll_y);
         })
     }
@@ -168,6 +172,13 @@ impl<L: Lens<Target = ScrollData>> ScrollView<L> {
     }
 }
 
+impl<'a, L: Lens<Target = ScrollData>> Handle<'a, ScrollView<L>> {
+    /// Sets the distance in pixels scrolled by one line of mouse wheel movement.
+    pub fn scroll_sensitivity(self, sensitivity: f32) -> Self {
+        self.modify(|scrollview: &mut ScrollView<L>| scrollview.scroll_sensitivity = sensitivity)
+    }
+}
+
 impl<L: Lens<Target = ScrollData>> View for ScrollView<L> {
     fn element(&self) -> Option<&'static str> {
         Some("scrollview")
@@ -194,13 +205,13 @@ impl<L: Lens<Target = ScrollData>> View for ScrollView<L> {
                 let data = self.data.get(cx);
                 if x != 0.0 {
                     let negative_space = data.child_x - data.parent_x;
-                    let logical_delta = x * SCROLL_SENSITIVITY / negative_space;
+                    let logical_delta = x * self.scroll_sensitivity / negative_space;
                     cx.emit(ScrollEvent::ScrollX(logical_delta));
                 }
                 let data = cx.data::<ScrollData>().unwrap();
                 if y != 0.0 {
                     let negative_space = data.child_y - data.parent_y;
-                    let logical_delta = y * SCROLL_SENSITIVITY / negative_space;
+                    let logical_delta = y * self.scroll_sensitivity / negative_space;
                     cx.emit(ScrollEvent::ScrollY(logical_delta));
                 }
             }
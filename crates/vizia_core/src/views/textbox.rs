@@ -1,43 +1,235 @@
 use crate::accessibility::IntoNode;
 use crate::cache::BoundingBox;
 use crate::context::AccessNode;
+use crate::fonts::icons_names;
 use crate::prelude::*;
 
-use crate::text::{enforce_text_bounds, ensure_visible, Direction, Movement};
+use crate::text::{
+    cursor_to_offset, enforce_text_bounds, ensure_visible, find_matches, offset_to_cursor,
+    remap_offset, set_editor_cursor, unwiden_x, widen_span, widened_spaces, widened_width,
+    Direction, Movement, Spacing,
+};
+#[cfg(feature = "regex")]
+use crate::text::{find_regex_matches, regex_replacements};
 use crate::views::scrollview::SCROLL_SENSITIVITY;
-use accesskit::{ActionData, ActionRequest, Rect, TextDirection, TextPosition, TextSelection};
-use cosmic_text::{Action, Attrs, Cursor, Edit};
-use std::sync::Arc;
+use crate::views::Orientation;
+use accesskit::{
+    ActionData, ActionRequest, NodeId, Rect, TextDirection, TextPosition, TextSelection,
+};
+use cosmic_text::{
+    Action, Attrs, AttrsOwned, Buffer, Color as CosmicColor, Cursor, Edit, LayoutRunIter,
+};
+use instant::{Duration, Instant};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ops::Range;
+use std::sync::{Arc, Condvar, Mutex};
+use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
 use vizia_id::GenerationalId;
 use vizia_input::Code;
-use vizia_storage::TreeExt;
+use vizia_storage::{TreeExt, TreeIterator};
+
+/// How long the text must be left untouched before the highlighter is run again.
+const HIGHLIGHT_DELAY: Duration = Duration::from_millis(150);
+
+/// How long the text must be left untouched before the spellchecker is run again.
+const SPELLCHECK_DELAY: Duration = Duration::from_millis(400);
+
+/// How far, in logical pixels, the mouse must move after pressing inside the selection before the
+/// selected text starts being dragged.
+const MOVE_THRESHOLD: f32 = 4.0;
+
+/// The opening and closing characters completed by [`Handle::auto_pairs`].
+const AUTO_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+/// The bracket pairs that `TextEvent::ExpandSelection` selects between.
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// The text added to the start of each line by [`TextEvent::Indent`].
+const INDENT: &str = "    ";
 
 #[derive(Lens)]
 pub struct TextboxData {
-    edit: bool,
+    /// Whether the text is being edited, which is the case while the textbox has focus. Views
+    /// inside the textbox can bind to `TextboxData::edit` to style themselves while it is focused.
+    pub edit: bool,
+    /// Whether the text has been edited since it was last loaded or submitted, for example to
+    /// enable a save button. Views can bind to `TextboxData::is_dirty`. Editing the text back to
+    /// its loaded value by hand doesn't clear the flag, as that would mean comparing the whole
+    /// text on every keystroke.
+    pub is_dirty: bool,
+    /// The text as it was last loaded or submitted, as returned by `clone_text`.
+    committed_text: String,
     transform: (f32, f32),
+    scroll_y: f32,
+    scroll_ratio: f32,
+    line_numbers: bool,
+    line_number_text: String,
+    measured: Option<((i32, i32), (f32, f32))>,
+    /// The text as returned by `clone_text`, joined from the lines of the buffer the first time it
+    /// is asked for after an edit.
+    text_cache: RefCell<Option<String>>,
+    extra_cursors: Vec<(usize, usize)>,
+    wrap_column: Option<usize>,
+    ruler_x: Option<f32>,
+    auto_height: bool,
+    min_rows: usize,
+    max_rows: Option<usize>,
     content_entity: Entity,
     kind: TextboxKind,
+    #[lens(ignore)]
+    selection_mode: SelectionMode,
+    selection_anchor: Range<usize>,
+    move_selection: Option<Range<usize>>,
+    /// The caret offset reached by the last vertical movement and the horizontal position it set
+    /// out from, which further vertical movements from there keep to.
+    goal_x: Option<(usize, f32)>,
+    moving_selection: bool,
+    /// Whether an edit was made by the batch of events being applied, if any.
+    batch: Option<bool>,
+    preedit: Option<Range<usize>>,
+    dead_key: Option<char>,
+    base_direction: Option<BaseDirection>,
+    newline_replacement: Option<char>,
+    smart_home: bool,
+    auto_pairs: bool,
+    text_align: TextAlign,
+    line_ending: Option<LineEnding>,
+    loaded_line_ending: LineEnding,
+    overwrite: bool,
+    cut_line_when_empty: bool,
+    trim_on_submit: bool,
+    submit_only_if_changed: bool,
+    scroll_to_caret_on_reset: bool,
+    scroll_sensitivity: f32,
+    selectable: bool,
+    key_bindings: KeyBindings,
+    empty: bool,
+    rtl: bool,
+    /// The number of user-perceived characters (grapheme clusters) in the text.
+    pub char_count: usize,
+    /// The number of words in the text.
+    pub word_count: usize,
+    context_menu_open: bool,
+    lock_focus: bool,
+    context_menu_position: (f32, f32),
+    has_selection: bool,
+    last_selection: Option<(usize, usize)>,
+    can_paste: bool,
     on_edit: Option<Arc<dyn Fn(&mut EventContext, String) + Send + Sync>>,
-    on_submit: Option<Arc<dyn Fn(&mut EventContext, String, bool) + Send + Sync>>,
+    on_edit_ref: Option<Arc<dyn Fn(&mut EventContext, &dyn Fn() -> String) + Send + Sync>>,
+    on_change: Option<Arc<dyn Fn(&mut EventContext, EditDelta) + Send + Sync>>,
+    on_edit_debounced: Option<(Duration, Arc<dyn Fn(&mut EventContext, String) + Send + Sync>)>,
+    edit_generation: usize,
+    on_highlight: Option<Arc<dyn Fn(&str) -> Vec<(Range<usize>, Color)> + Send + Sync>>,
+    highlight_generation: usize,
+    on_spellcheck: Option<Arc<dyn Fn(&str) -> Vec<Range<usize>> + Send + Sync>>,
+    spellcheck_generation: usize,
+    timer: EditTimer,
+    on_submit: Option<Arc<dyn Fn(&mut EventContext, String, SubmitReason) + Send + Sync>>,
+    on_cancel: Option<Arc<dyn Fn(&mut EventContext) + Send + Sync>>,
+    on_selection_change:
+        Option<Arc<dyn Fn(&mut EventContext, Option<(usize, usize)>) + Send + Sync>>,
+    on_clipboard_error: Option<Arc<dyn Fn(&mut EventContext, &dyn Error) + Send + Sync>>,
+    on_copy: Option<Arc<dyn Fn(&mut EventContext, &str) -> ClipboardData + Send + Sync>>,
 }
 
 impl TextboxData {
     pub fn new() -> Self {
         Self {
             edit: false,
+            is_dirty: false,
+            committed_text: String::new(),
             transform: (0.0, 0.0),
+            scroll_y: 0.0,
+            scroll_ratio: 1.0,
+            line_numbers: false,
+            line_number_text: String::new(),
+            measured: None,
+            text_cache: RefCell::new(None),
+            extra_cursors: Vec::new(),
+            wrap_column: None,
+            ruler_x: None,
+            auto_height: false,
+            min_rows: 1,
+            max_rows: None,
             on_edit: None,
+            on_edit_ref: None,
+            on_change: None,
+            on_edit_debounced: None,
+            edit_generation: 0,
+            on_highlight: None,
+            highlight_generation: 0,
+            on_spellcheck: None,
+            spellcheck_generation: 0,
+            timer: EditTimer::default(),
             content_entity: Entity::null(),
             kind: TextboxKind::SingleLine,
+            selection_mode: SelectionMode::Char,
+            selection_anchor: 0..0,
+            move_selection: None,
+            goal_x: None,
+            moving_selection: false,
+            batch: None,
+            preedit: None,
+            dead_key: None,
+            base_direction: None,
+            newline_replacement: Some(' '),
+            smart_home: false,
+            auto_pairs: false,
+            text_align: TextAlign::Left,
+            line_ending: None,
+            loaded_line_ending: LineEnding::Lf,
+            overwrite: false,
+            cut_line_when_empty: true,
+            trim_on_submit: false,
+            submit_only_if_changed: false,
+            scroll_to_caret_on_reset: false,
+            scroll_sensitivity: SCROLL_SENSITIVITY,
+            selectable: true,
+            key_bindings: KeyBindings::new(false),
+            empty: true,
+            rtl: false,
+            char_count: 0,
+            word_count: 0,
+            context_menu_open: false,
+            lock_focus: false,
+            context_menu_position: (0.0, 0.0),
+            has_selection: false,
+            last_selection: None,
+            can_paste: false,
             on_submit: None,
+            on_cancel: None,
+            on_selection_change: None,
+            on_clipboard_error: None,
+            on_copy: None,
         }
     }
 
+    /// Creates the data of a textbox whose text is held by `content_entity`, which can be any
+    /// entity, and sets its text. This allows the editing methods to be used without building a
+    /// [`Textbox`], for example to test them in a headless [`Context`].
+    pub fn with_content(
+        cx: &mut EventContext,
+        content_entity: Entity,
+        text: &str,
+        multiline: bool,
+    ) -> Self {
+        let kind =
+            if multiline { TextboxKind::MultiLineUnwrapped } else { TextboxKind::SingleLine };
+        let mut data =
+            Self { content_entity, kind, key_bindings: KeyBindings::new(multiline), ..Self::new() };
+        data.reset_text(cx, text);
+        data.committed_text = data.clone_text(cx);
+
+        data
+    }
+
     fn set_caret(&mut self, cx: &mut EventContext) {
         let entity = self.content_entity;
-        if entity == Entity::null() {
+        if entity == Entity::null() || self.batch.is_some() {
             return;
         }
         let parent = entity.parent(cx.tree).unwrap();
@@ -58,952 +250,5742 @@ impl TextboxData {
         ty *= scale;
         (tx, ty) = enforce_text_bounds(&bounds, &parent_bounds, (tx, ty));
 
-        // TODO justify????
-        if let Some((x, y, w, h)) = cx.text_context.layout_caret(
-            self.content_entity,
-            (bounds.x, bounds.y),
-            (0., 0.),
-            1.0 * scale,
-        ) {
-            let caret_box = BoundingBox { x, y, w, h };
-
+        if let Some(caret_box) = self.layout_caret(cx) {
             parent_bounds.x -= 1.0;
             parent_bounds.w += 2.0;
             (tx, ty) = ensure_visible(&caret_box, &parent_bounds, (tx, ty));
         }
 
         self.transform = (tx.round() / scale, ty.round() / scale);
-    }
+        self.update_scrollbar(&bounds, &parent_bounds, ty);
 
-    pub fn insert_text(&mut self, cx: &mut EventContext, text: &str) {
-        cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.insert_string(text, None);
+        if self.line_numbers {
+            self.update_line_numbers(cx);
+        }
+
+        self.ruler_x = self.wrap_column.and_then(|column| {
+            let textbox = parent.parent(cx.tree)?;
+            let textbox_bounds = *cx.cache.bounds.get(textbox)?;
+            let advance = cx.text_context.average_advance(entity);
+            let x = bounds.x + tx + column as f32 * advance;
+            // The ruler is hidden while it is scrolled out of view.
+            (x >= parent_bounds.x && x <= parent_bounds.x + parent_bounds.w)
+                .then(|| (x - textbox_bounds.x) / scale)
         });
-        cx.style.needs_text_layout.insert(self.content_entity, true).unwrap();
+
+        self.update_height(cx);
+
+        // Keep the candidate window of an input method next to the caret.
+        if self.edit {
+            if let Some(caret) = self.caret_rect(cx) {
+                cx.emit(WindowEvent::SetImePosition(caret.x, caret.y + caret.h));
+            }
+        }
+
+        self.notify_selection_change(cx);
     }
 
-    pub fn delete_text(&mut self, cx: &mut EventContext, movement: Movement) {
-        if cx.text_context.with_editor(self.content_entity, |buf| !buf.delete_selection()) {
-            self.move_cursor(cx, movement, true);
-            cx.text_context.with_editor(self.content_entity, |buf| {
-                buf.delete_selection();
-            });
+    /// Calls the `on_change` callback with an edit that replaced `range` with `text`.
+    fn notify_change(&mut self, cx: &mut EventContext, range: Range<usize>, text: &str) {
+        if range.is_empty() && text.is_empty() {
+            return;
+        }
+
+        if let Some(callback) = self.on_change.take() {
+            (callback)(cx, EditDelta { range, text: text.to_owned() });
+
+            self.on_change = Some(callback);
         }
-        cx.style.needs_text_layout.insert(self.content_entity, true).unwrap();
     }
 
-    pub fn reset_text(&mut self, cx: &mut EventContext, text: &str) {
-        cx.text_context.with_buffer(self.content_entity, |buf| {
-            buf.set_text(text, Attrs::new());
-        });
-        cx.style.needs_text_layout.insert(self.content_entity, true).unwrap();
+    /// Calls the `on_selection_change` callback if the selected range differs from the one it was
+    /// last called with.
+    fn notify_selection_change(&mut self, cx: &mut EventContext) {
+        let range = self.selection_range(cx);
+        let selection = if range.is_empty() { None } else { Some((range.start, range.end)) };
+        if selection == self.last_selection {
+            return;
+        }
+        self.last_selection = selection;
+
+        if let Some(callback) = self.on_selection_change.take() {
+            (callback)(cx, selection);
+
+            self.on_selection_change = Some(callback);
+        }
     }
 
-    pub fn move_cursor(&mut self, cx: &mut EventContext, movement: Movement, selection: bool) {
-        cx.text_context.with_editor(self.content_entity, |buf| {
-            if selection {
-                if buf.select_opt().is_none() {
-                    buf.set_select_opt(Some(buf.cursor()));
-                }
-            } else {
-                buf.set_select_opt(None);
+    /// Lays out the caret within the untranslated content, in physical pixels.
+    fn layout_caret(&self, cx: &mut EventContext) -> Option<BoundingBox> {
+        let bounds = *cx.cache.bounds.get(self.content_entity)?;
+        let scale = cx.style.dpi_factor as f32;
+        let justify = self.text_align.justify();
+        let width = match cx.style.caret_width.get(self.content_entity) {
+            Some(Units::Pixels(width)) => *width,
+            _ => 1.0,
+        };
+        cx.text_context
+            .layout_caret(
+                self.content_entity,
+                (bounds.x + bounds.w * justify, bounds.y),
+                (justify, 0.),
+                width * scale,
+            )
+            .map(|(x, y, w, h)| BoundingBox { x, y, w, h })
+    }
+
+    /// Returns the bounds of the caret in physical window coordinates, taking scrolling into
+    /// account, for example to anchor a suggestion popup below it.
+    pub fn caret_rect(&self, cx: &mut EventContext) -> Option<BoundingBox> {
+        if self.content_entity == Entity::null() {
+            return None;
+        }
+
+        let scale = cx.style.dpi_factor as f32;
+        let (tx, ty) = self.transform;
+        self.layout_caret(cx).map(|caret| BoundingBox {
+            x: caret.x + tx * scale,
+            y: caret.y + ty * scale,
+            ..caret
+        })
+    }
+
+    /// Returns the logical width and height of the laid out text, at least one line tall. The
+    /// result is kept until the text, the width it is wrapped at or the line height changes.
+    pub fn measure(&mut self, cx: &mut EventContext) -> (f32, f32) {
+        if self.content_entity == Entity::null() {
+            return (0.0, 0.0);
+        }
+
+        let scale = cx.style.dpi_factor as f32;
+        let measured = self.measured;
+        // Justified lines fill the width anyway, so only advancing tabs makes the text wider.
+        let spacing = Spacing { justified: false, ..cx.text_context.spacing(self.content_entity) };
+        let (key, size) = cx.text_context.with_buffer(self.content_entity, |buf| {
+            let key = (buf.size().0, buf.metrics().line_height);
+            match measured {
+                Some((measured_key, size)) if measured_key == key => return (key, size),
+                _ => {}
             }
 
-            buf.action(match movement {
-                Movement::Grapheme(Direction::Upstream) => Action::Previous,
-                Movement::Grapheme(Direction::Downstream) => Action::Next,
-                Movement::Grapheme(Direction::Left) => Action::Left,
-                Movement::Grapheme(Direction::Right) => Action::Right,
-                Movement::Word(Direction::Upstream) => Action::PreviousWord,
-                Movement::Word(Direction::Downstream) => Action::NextWord,
-                Movement::Word(Direction::Left) => Action::LeftWord,
-                Movement::Word(Direction::Right) => Action::RightWord,
-                Movement::Line(Direction::Upstream) => Action::Up,
-                Movement::Line(Direction::Downstream) => Action::Down,
-                Movement::LineStart => Action::Home,
-                Movement::LineEnd => Action::End,
-                Movement::Page(dir) => {
-                    let parent = self.content_entity.parent(cx.tree).unwrap();
-                    let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
-                    let sign = if let Direction::Upstream = dir { -1 } else { 1 };
-                    Action::Vertical(sign * parent_bounds.h as i32)
-                }
-                Movement::Body(Direction::Upstream) => Action::BufferStart,
-                Movement::Body(Direction::Downstream) => Action::BufferEnd,
-                _ => return,
+            buf.shape_until_scroll();
+            let line_height = buf.metrics().line_height as f32;
+            let (width, rows) = buf.layout_runs().fold((0.0f32, 0), |(width, rows), run| {
+                let spaces = widened_spaces(buf, &run, spacing);
+                (width.max(widened_width(&run, &spaces)), rows + 1)
             });
+            (key, (width / scale, rows.max(1) as f32 * line_height / scale))
         });
-        cx.needs_redraw();
+        self.measured = Some((key, size));
+        size
     }
 
-    pub fn select_all(&mut self, cx: &mut EventContext) {
-        cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.action(Action::BufferStart);
-            buf.set_select_opt(Some(buf.cursor()));
-            buf.action(Action::BufferEnd);
-        });
-        cx.needs_redraw();
+    /// Limits the height of the textbox to fit its text, between `min_rows` and `max_rows` lines,
+    /// when `auto_height` is enabled. The minimum and maximum height are set rather than the
+    /// height, and only while the height is automatic, so that a height set by the application
+    /// or a style sheet takes precedence.
+    fn update_height(&mut self, cx: &mut EventContext) {
+        if !self.auto_height || self.content_entity == Entity::null() {
+            return;
+        }
+
+        let container = self.content_entity.parent(cx.tree).unwrap();
+        let textbox = container.parent(cx.tree).unwrap();
+        if !matches!(cx.style.height.get(textbox), None | Some(Units::Auto)) {
+            return;
+        }
+
+        let scale = cx.style.dpi_factor as f32;
+
+        let (_, mut height) = self.measure(cx);
+        let line_height = cx
+            .text_context
+            .with_buffer(self.content_entity, |buf| buf.metrics().line_height as f32 / scale);
+        if let Some(max_rows) = self.max_rows {
+            height = height.min(max_rows.max(self.min_rows).max(1) as f32 * line_height);
+        }
+        height = height.max(self.min_rows as f32 * line_height);
+
+        // Keep the padding and borders around the text container.
+        let container_height = cx.cache.get_height(container);
+        let textbox_height = cx.cache.get_height(textbox);
+        let height = Pixels((height + (textbox_height - container_height) / scale).round());
+        if cx.style.min_height.get_inline(textbox) != Some(&height)
+            || cx.style.max_height.get_inline(textbox) != Some(&height)
+        {
+            cx.style.min_height.insert(textbox, height);
+            cx.style.max_height.insert(textbox, height);
+            cx.needs_relayout();
+        }
     }
 
-    pub fn select_word(&mut self, cx: &mut EventContext) {
-        cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.action(Action::PreviousWord);
-            buf.set_select_opt(Some(buf.cursor()));
-            buf.action(Action::NextWord);
+    /// Updates the text of the line number gutter, which has a row for each row of the text so
+    /// that the numbers line up with the lines. Wrapped lines are numbered at their first row.
+    fn update_line_numbers(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        self.line_number_text = cx.text_context.with_buffer(self.content_entity, |buf| {
+            buf.shape_until_scroll();
+            let mut runs = vec![0; buf.lines.len()];
+            for run in buf.layout_runs() {
+                runs[run.line_i] += 1;
+            }
+
+            let mut text = String::new();
+            for (index, count) in runs.into_iter().enumerate() {
+                if index > 0 {
+                    text.push('\n');
+                }
+                text.push_str(&(index + 1).to_string());
+                text.extend(std::iter::repeat('\n').take(count.max(1) - 1));
+            }
+            text
         });
-        cx.needs_redraw();
     }
 
-    pub fn select_paragraph(&mut self, cx: &mut EventContext) {
-        cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.action(Action::ParagraphStart);
-            buf.set_select_opt(Some(buf.cursor()));
-            buf.action(Action::ParagraphEnd);
-        });
-        cx.needs_redraw();
+    /// Updates the position and size of the scrollbar thumb from the vertical translation `ty` of
+    /// the content, in physical pixels.
+    fn update_scrollbar(&mut self, bounds: &BoundingBox, parent_bounds: &BoundingBox, ty: f32) {
+        let overflow = bounds.h - parent_bounds.h;
+        if overflow > 0.0 {
+            self.scroll_y = (-ty / overflow).clamp(0.0, 1.0);
+            self.scroll_ratio = parent_bounds.h / bounds.h;
+        } else {
+            self.scroll_y = 0.0;
+            self.scroll_ratio = 1.0;
+        }
     }
 
-    pub fn deselect(&mut self, cx: &mut EventContext) {
+    pub fn insert_text(&mut self, cx: &mut EventContext, text: &str) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        if self.overwrite
+            && cx.text_context.with_editor(self.content_entity, |buf| buf.select_opt().is_none())
+        {
+            // Each inserted grapheme cluster replaces one after the cursor, stopping at the end
+            // of the line.
+            let contents = self.clone_text(cx);
+            let offset = self.selection_range(cx).start;
+            let count = text.graphemes(true).count();
+            let end = contents[offset..]
+                .grapheme_indices(true)
+                .take_while(|(_, grapheme)| *grapheme != "\n")
+                .take(count)
+                .last()
+                .map_or(offset, |(index, grapheme)| offset + index + grapheme.len());
+            self.select_between(cx, offset, end);
+        }
+
+        let removed = self.selection_range(cx);
+        let counted = self.count_lines(cx, removed.clone());
         cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.set_select_opt(None);
+            buf.insert_string(text, None);
         });
-        cx.needs_redraw();
+        self.remap(cx, removed.clone(), text.len());
+        self.text_changed(cx);
+        self.update_counts(cx, counted, removed.start..removed.start + text.len());
+        self.update_content_flags(cx);
+        self.notify_change(cx, removed, text);
     }
 
-    /// These input coordinates should be physical coordinates, i.e. what the mouse events provide.
-    /// The output text coordinates will also be physical, but relative to the top of the text
-    /// glyphs, appropriate for passage to cosmic.
-    pub fn coordinates_global_to_text(&self, cx: &EventContext, x: f32, y: f32) -> (f32, f32) {
-        let parent = self.content_entity.parent(cx.tree).unwrap();
-        let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
+    /// Inserts `text`, completing brackets and quotes when it is a single character. An opening
+    /// character wraps the selection in the pair, leaving the wrapped text selected, or inserts both
+    /// characters with the cursor between them. Typing a closing character in front of the same
+    /// character moves the cursor over it instead.
+    fn insert_paired(&mut self, cx: &mut EventContext, text: &str) {
+        let mut chars = text.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return self.insert_text(cx, text),
+        };
+
+        let range = self.selection_range(cx);
+        let contents = self.clone_text(cx);
+        if range.is_empty()
+            && contents[range.end..].starts_with(c)
+            && AUTO_PAIRS.iter().any(|(_, close)| *close == c)
+        {
+            self.move_cursor(cx, Movement::Grapheme(Direction::Downstream), false);
+            return;
+        }
 
-        let x = x - self.transform.0 * cx.style.dpi_factor as f32 - parent_bounds.x;
-        let y = y - self.transform.1 * cx.style.dpi_factor as f32 - parent_bounds.y;
-        (x, y)
+        if let Some((open, close)) = AUTO_PAIRS.iter().find(|(open, _)| *open == c) {
+            let selected = &contents[range.clone()];
+            self.insert_text(cx, &format!("{}{}{}", open, selected, close));
+            let start = range.start + open.len_utf8();
+            self.select_range(cx, start..start + selected.len());
+        } else {
+            self.insert_text(cx, text);
+        }
     }
 
-    /// This function takes window-global physical coordinates.
-    pub fn hit(&mut self, cx: &mut EventContext, x: f32, y: f32) {
-        let (x, y) = self.coordinates_global_to_text(cx, x, y);
-        cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.action(Action::Click { x: x as i32, y: y as i32 })
-        });
-        cx.needs_redraw();
-    }
+    /// Shows `text`, which an input method is composing, underlined at the cursor in place of any
+    /// previous composition. `cursor` is the byte range of the cursor within `text`. The composed
+    /// text is left out of `clone_text` until it is committed.
+    pub fn set_preedit(
+        &mut self,
+        cx: &mut EventContext,
+        text: &str,
+        cursor: Option<(usize, usize)>,
+    ) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
 
-    /// This function takes window-global physical coordinates.
-    pub fn drag(&mut self, cx: &mut EventContext, x: f32, y: f32) {
-        let (x, y) = self.coordinates_global_to_text(cx, x, y);
+        self.clear_preedit(cx);
+        if text.is_empty() {
+            return;
+        }
+
+        let offset = self.selection_range(cx).start;
         cx.text_context.with_editor(self.content_entity, |buf| {
-            buf.action(Action::Drag { x: x as i32, y: y as i32 })
+            set_editor_cursor(buf, offset_to_cursor(buf.buffer(), offset));
+            buf.set_select_opt(None);
+            buf.insert_string(text, None);
         });
-        cx.needs_redraw();
-    }
+        self.remap(cx, offset..offset, text.len());
+        self.text_changed(cx);
 
-    /// This function takes window-global physical dimensions.
-    pub fn scroll(&mut self, cx: &mut EventContext, x: f32, y: f32) {
-        let entity = self.content_entity;
-        let parent = cx.tree.get_parent(entity).unwrap();
-        let bounds = *cx.cache.bounds.get(entity).unwrap();
-        let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
-        let (mut tx, mut ty) = self.transform;
-        let scale = cx.style.dpi_factor as f32;
-        tx *= scale;
-        ty *= scale;
-        tx += x * SCROLL_SENSITIVITY;
-        ty += y * SCROLL_SENSITIVITY;
-        (tx, ty) = enforce_text_bounds(&bounds, &parent_bounds, (tx, ty));
-        self.transform = (tx / scale, ty / scale);
-    }
+        let range = offset..offset + text.len();
+        cx.text_context.set_preedit(self.content_entity, Some(range.clone()));
+        self.preedit = Some(range);
 
-    #[allow(dead_code)]
-    pub fn clone_selected(&self, cx: &mut EventContext) -> Option<String> {
-        cx.text_context.with_editor(self.content_entity, |buf| buf.copy_selection())
+        let caret = offset + cursor.map_or(text.len(), |(_, end)| end);
+        self.select_range(cx, caret..caret);
     }
 
-    pub fn clone_text(&self, cx: &mut EventContext) -> String {
-        cx.text_context.with_buffer(self.content_entity, |buf| {
-            buf.lines.iter().map(|line| line.text()).collect::<Vec<_>>().join("\n")
-        })
+    /// Removes the text being composed by an input method, leaving the cursor where it started.
+    fn clear_preedit(&mut self, cx: &mut EventContext) {
+        if let Some(range) = self.preedit.take() {
+            cx.text_context.with_editor(self.content_entity, |buf| {
+                set_editor_cursor(buf, offset_to_cursor(buf.buffer(), range.end));
+                buf.set_select_opt(Some(offset_to_cursor(buf.buffer(), range.start)));
+                buf.delete_selection();
+            });
+            self.remap(cx, range, 0);
+            cx.text_context.set_preedit(self.content_entity, None);
+            self.text_changed(cx);
+        }
     }
-}
-
-pub enum TextEvent {
-    InsertText(String),
-    ResetText(String),
-    DeleteText(Movement),
-    MoveCursor(Movement, bool),
-    SelectAll,
-    SelectWord,
-    SelectParagraph,
-    //SetSelection(Selection),
-    StartEdit,
-    EndEdit,
-    Submit(bool),
-    Hit(f32, f32),
-    Drag(f32, f32),
-    Scroll(f32, f32),
-    Copy,
-    Paste,
-    Cut,
 
-    // Helpers
-    SetOnEdit(Option<Arc<dyn Fn(&mut EventContext, String) + Send + Sync>>),
-    SetOnSubmit(Option<Arc<dyn Fn(&mut EventContext, String, bool) + Send + Sync>>),
-    InitContent(Entity, TextboxKind),
-    GeometryChanged,
-}
+    pub fn delete_text(&mut self, cx: &mut EventContext, movement: Movement) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
 
-impl Model for TextboxData {
-    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|text_event, _| match text_event {
-            TextEvent::InsertText(text) => {
-                if self.edit {
-                    self.insert_text(cx, text);
-                    self.set_caret(cx);
+        if cx.text_context.with_editor(self.content_entity, |buf| buf.select_opt().is_none()) {
+            if let Movement::LineStart | Movement::LineEnd = movement {
+                // Select up to the logical edge of the visual line rather than moving to its visual
+                // edge, which in right-to-left text lies on the other side of the caret.
+                let end = matches!(movement, Movement::LineEnd);
+                let selected = cx.text_context.with_editor(self.content_entity, |buf| {
+                    let cursor = buf.cursor();
+                    let index = match run_at(buf.buffer(), cursor) {
+                        Some((range, _)) if end => range.end,
+                        Some((range, _)) => range.start,
+                        None => return false,
+                    };
+                    if index == cursor.index {
+                        return false;
+                    }
 
-                    if let Some(callback) = self.on_edit.take() {
-                        let text = self.clone_text(cx);
-                        (callback)(cx, text);
+                    buf.set_select_opt(Some(cursor));
+                    set_editor_cursor(buf, Cursor::new(cursor.line, index));
+                    true
+                });
 
-                        self.on_edit = Some(callback);
+                // At the edge of the line the line break is deleted instead.
+                if !selected {
+                    let direction = if end { Direction::Downstream } else { Direction::Upstream };
+                    self.move_cursor(cx, Movement::Grapheme(direction), true);
+                }
+            } else if let Movement::Grapheme(Direction::Upstream | Direction::Downstream) = movement
+            {
+                // Delete a whole grapheme cluster, such as an emoji sequence joined by zero-width
+                // joiners or a letter with its combining marks, rather than a single code point.
+                let upstream = matches!(movement, Movement::Grapheme(Direction::Upstream));
+                let selected = cx.text_context.with_editor(self.content_entity, |buf| {
+                    let cursor = buf.cursor();
+                    let text = buf.buffer().lines[cursor.line].text();
+                    let index = if upstream {
+                        text[..cursor.index]
+                            .grapheme_indices(true)
+                            .next_back()
+                            .map(|(index, _)| index)
+                    } else {
+                        text[cursor.index..]
+                            .graphemes(true)
+                            .next()
+                            .map(|grapheme| cursor.index + grapheme.len())
+                    };
+                    match index {
+                        Some(index) => {
+                            buf.set_select_opt(Some(cursor));
+                            set_editor_cursor(buf, Cursor::new(cursor.line, index));
+                            true
+                        }
+                        None => false,
                     }
+                });
+
+                // At the edge of the line the line break is deleted instead.
+                if !selected {
+                    self.move_cursor(cx, movement, true);
                 }
+            } else {
+                self.move_cursor(cx, movement, true);
             }
+        }
+        let removed = self.selection_range(cx);
+        let counted = self.count_lines(cx, removed.clone());
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.delete_selection();
+        });
+        self.remap(cx, removed.clone(), 0);
+        self.text_changed(cx);
+        self.update_counts(cx, counted, removed.start..removed.start);
+        self.update_content_flags(cx);
+        self.notify_change(cx, removed, "");
+    }
 
-            TextEvent::ResetText(text) => {
-                self.reset_text(cx, text);
-                self.scroll(cx, 0.0, 0.0); // ensure_visible
-            }
+    /// Swaps the grapheme clusters on either side of the cursor and moves the cursor past them. At
+    /// the end of a line the two grapheme clusters before the cursor are swapped instead. Does
+    /// nothing at the start of the text.
+    pub fn transpose_chars(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
 
-            TextEvent::DeleteText(movement) => {
-                if self.edit {
-                    self.delete_text(cx, *movement);
-                    self.set_caret(cx);
+        // The cursor counts the text being composed, which `clone_text` leaves out.
+        self.clear_preedit(cx);
 
-                    if let Some(callback) = self.on_edit.take() {
-                        let text = self.clone_text(cx);
-                        (callback)(cx, text);
+        let text = self.clone_text(cx);
+        let mut offset = cx
+            .text_context
+            .with_editor(self.content_entity, |buf| cursor_to_offset(buf.buffer(), buf.cursor()));
 
-                        self.on_edit = Some(callback);
-                    }
-                }
+        let at_line_end = text[offset..].chars().next().map_or(true, |c| c == '\n');
+        if at_line_end {
+            if let Some((start, _)) = text[..offset].grapheme_indices(true).next_back() {
+                offset = start;
             }
+        }
 
-            TextEvent::MoveCursor(movement, selection) => {
-                if self.edit {
-                    self.move_cursor(cx, *movement, *selection);
-                    self.set_caret(cx);
-                }
-            }
+        let before = text[..offset].grapheme_indices(true).next_back();
+        let after = text[offset..].graphemes(true).next();
+        if let (Some((start, first)), Some(second)) = (before, after) {
+            let swapped = format!("{}{}", second, first);
+            self.select_between(cx, start, offset + second.len());
+            self.insert_text(cx, &swapped);
+        }
+    }
 
-            TextEvent::StartEdit => {
-                if !cx.is_disabled() && !self.edit {
+    /// Replaces the word around the cursor with `suggestion`, or inserts it at the cursor if the
+    /// cursor is not next to a word.
+    pub fn accept_suggestion(&mut self, cx: &mut EventContext, suggestion: &str) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        // The cursor counts the text being composed, which `clone_text` leaves out.
+        self.clear_preedit(cx);
+
+        let text = self.clone_text(cx);
+        let offset = cx
+            .text_context
+            .with_editor(self.content_entity, |buf| cursor_to_offset(buf.buffer(), buf.cursor()));
+
+        let word = word_range_at(&text, offset.saturating_sub(1));
+        if offset > 0 && text[word.clone()].chars().any(char::is_alphanumeric) {
+            self.replace_range(cx, word, suggestion);
+        } else {
+            self.replace_range(cx, offset..offset, suggestion);
+        }
+    }
+
+    /// Replaces the given byte range of the text returned by `clone_text` with `text` in a single
+    /// edit, leaving the caret after the inserted text. The ends of the range are moved back to
+    /// character boundaries where needed.
+    pub fn replace_range(&mut self, cx: &mut EventContext, range: Range<usize>, text: &str) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let contents = self.clone_text(cx);
+        let start = floor_char_boundary(&contents, range.start);
+        let end = floor_char_boundary(&contents, range.end).max(start);
+        self.select_range(cx, start..end);
+        let counted = self.count_lines(cx, start..end);
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.insert_string(text, None);
+        });
+        self.remap(cx, start..end, text.len());
+        self.text_changed(cx);
+        self.update_counts(cx, counted, start..start + text.len());
+        self.update_content_flags(cx);
+        self.notify_change(cx, start..end, text);
+    }
+
+    /// Inserts `text` at the given byte offset of the text returned by `clone_text`, for example to
+    /// apply an edit made elsewhere. The caret and selection stay on the same text, moving along
+    /// with it when the insertion is before them. The offset is moved back to a character boundary
+    /// where needed.
+    pub fn insert_at(&mut self, cx: &mut EventContext, offset: usize, text: &str) {
+        let offset = floor_char_boundary(&self.clone_text(cx), offset);
+        let (anchor, cursor) = self.cursor_offsets(cx);
+        self.replace_range(cx, offset..offset, text);
+        let shift =
+            |position: usize| if position > offset { position + text.len() } else { position };
+        self.select_between(cx, shift(anchor), shift(cursor));
+    }
+
+    /// Returns the byte range of the line containing the cursor along with its line break, and the
+    /// text of the line followed by a line break. Returns `None` if there is no text.
+    fn line_to_cut(&self, cx: &mut EventContext) -> Option<(Range<usize>, String)> {
+        if self.content_entity == Entity::null() {
+            return None;
+        }
+
+        let text = self.clone_text(cx);
+        if text.is_empty() {
+            return None;
+        }
+
+        let (start, end) = cx.text_context.with_editor(self.content_entity, |buf| {
+            let line = buf.cursor().line;
+            let start = cursor_to_offset(buf.buffer(), Cursor::new(line, 0));
+            (start, start + buf.buffer().lines[line].text().len())
+        });
+
+        // The last line takes the preceding line break with it instead.
+        let range = if end < text.len() { start..end + 1 } else { start.saturating_sub(1)..end };
+        let line = if self.kind == TextboxKind::SingleLine {
+            text[start..end].to_owned()
+        } else {
+            format!("{}\n", &text[start..end])
+        };
+
+        Some((range, line))
+    }
+
+    /// Inserts text from the clipboard at every caret. With several carets and as many lines of
+    /// text, such as text cut from the same number of carets, each caret gets a line of its own.
+    #[cfg(feature = "clipboard")]
+    fn paste(&mut self, cx: &mut EventContext, text: String) {
+        let text = text.replace("\r\n", "\n");
+        let lines = text.split('\n').collect::<Vec<_>>();
+        if self.extra_cursors.is_empty() || lines.len() != self.extra_cursors.len() + 1 {
+            cx.emit(TextEvent::InsertText(text));
+            return;
+        }
+
+        self.clear_preedit(cx);
+        self.dead_key = None;
+        self.for_each_cursor(cx, |data, cx| {
+            let line = lines[data.cursor_rank(cx)];
+            data.insert_text(cx, line);
+        });
+        self.set_caret(cx);
+        self.notify_edit(cx);
+    }
+
+    /// Puts text copied or cut from the textbox on the clipboard, in the form returned by the
+    /// `on_copy` callback if there is one. Returns whether the clipboard was written.
+    #[cfg(feature = "clipboard")]
+    fn copy_to_clipboard(&mut self, cx: &mut EventContext, text: String) -> bool {
+        let text = self.line_ending().apply(text);
+        let data = match self.on_copy.take() {
+            Some(callback) => {
+                let data = (callback)(cx, &text);
+
+                self.on_copy = Some(callback);
+                data
+            }
+            None => ClipboardData::Text(text),
+        };
+
+        match data {
+            ClipboardData::Text(text) => match cx.set_clipboard(text) {
+                Ok(()) => true,
+                Err(error) => {
+                    self.clipboard_error(cx, error);
+                    false
+                }
+            },
+            ClipboardData::Handled => true,
+        }
+    }
+
+    /// Passes an error from the system clipboard to the `on_clipboard_error` callback, or prints it
+    /// if there is no callback.
+    #[cfg(feature = "clipboard")]
+    fn clipboard_error(&mut self, cx: &mut EventContext, error: Box<dyn Error + Send + Sync>) {
+        if let Some(callback) = self.on_clipboard_error.take() {
+            (callback)(cx, error.as_ref());
+
+            self.on_clipboard_error = Some(callback);
+        } else {
+            eprintln!("Clipboard error: {}", error);
+        }
+    }
+
+    /// Changes the case of the selected text, keeping it selected.
+    pub fn transform_selection(&mut self, cx: &mut EventContext, transform: CaseTransform) {
+        let range = self.selection_range(cx);
+        if range.is_empty() {
+            return;
+        }
+
+        let transformed = transform_case(&self.clone_text(cx)[range.clone()], transform);
+        self.insert_text(cx, &transformed);
+        self.select_range(cx, range.start..range.start + transformed.len());
+    }
+
+    /// Replaces the text, keeping the caret and selection at the same byte offsets as near as the
+    /// new text allows.
+    pub fn reset_text(&mut self, cx: &mut EventContext, text: &str) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        self.loaded_line_ending = LineEnding::detect(text);
+        let (anchor, cursor) = self.cursor_offsets(cx);
+        self.clear_extra_cursors(cx);
+        self.preedit = None;
+        cx.text_context.set_preedit(self.content_entity, None);
+        cx.text_context.with_buffer(self.content_entity, |buf| {
+            buf.set_text(text, Attrs::new());
+        });
+        cx.text_context.clear_spans(self.content_entity);
+        cx.text_context.set_match_highlights(self.content_entity, Vec::new());
+        cx.text_context.set_misspellings(self.content_entity, Vec::new());
+        self.text_changed(cx);
+        let text = self.clone_text(cx);
+        self.select_between(
+            cx,
+            floor_char_boundary(&text, anchor),
+            floor_char_boundary(&text, cursor),
+        );
+        // The word or line selected by a double or triple click may lie beyond the new text.
+        self.selection_anchor = floor_char_boundary(&text, self.selection_anchor.start)
+            ..floor_char_boundary(&text, self.selection_anchor.end);
+        self.move_selection = None;
+        self.count_all(cx);
+        self.update_content_flags(cx);
+    }
+
+    /// Applies styled spans to the contents of the textbox, replacing any previously set spans.
+    /// Ranges are byte offsets into the text returned by `clone_text`, and are shifted along with
+    /// the text as it is edited.
+    pub fn set_styled(&mut self, cx: &mut EventContext, spans: Vec<(Range<usize>, Attrs)>) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let spans =
+            spans.into_iter().map(|(range, attrs)| (range, AttrsOwned::new(attrs))).collect();
+        cx.text_context.set_spans(self.content_entity, spans);
+        cx.needs_redraw();
+    }
+
+    /// Returns the key bindings which map key chords to text events.
+    pub(crate) fn key_bindings(&self) -> &KeyBindings {
+        &self.key_bindings
+    }
+
+    /// Returns the selected byte range of the text returned by `clone_text`. If nothing is selected
+    /// this is an empty range at the position of the cursor.
+    pub fn selection_range(&self, cx: &mut EventContext) -> Range<usize> {
+        if self.content_entity == Entity::null() {
+            return 0..0;
+        }
+
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            let cursor = cursor_to_offset(buf.buffer(), buf.cursor());
+            let anchor = buf.select_opt().map(|select| cursor_to_offset(buf.buffer(), select));
+            let anchor = anchor.unwrap_or(cursor);
+            cursor.min(anchor)..cursor.max(anchor)
+        })
+    }
+
+    /// Counts the characters and words of the whole text, after it has been replaced.
+    fn count_all(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let text = self.clone_text(cx);
+        self.char_count = text.graphemes(true).count();
+        self.word_count = text.unicode_words().count();
+    }
+
+    /// Returns the number of grapheme clusters and words in the lines spanned by the given byte
+    /// range of the text, counting the line breaks between them as characters.
+    fn count_lines(&self, cx: &mut EventContext, range: Range<usize>) -> (usize, usize) {
+        if self.content_entity == Entity::null() {
+            return (0, 0);
+        }
+
+        cx.text_context.with_buffer(self.content_entity, |buf| {
+            let first = offset_to_cursor(buf, range.start).line;
+            let last = offset_to_cursor(buf, range.end).line;
+            buf.lines[first..=last].iter().fold((last - first, 0), |(chars, words), line| {
+                let text = line.text();
+                (chars + text.graphemes(true).count(), words + text.unicode_words().count())
+            })
+        })
+    }
+
+    /// Updates the character and word counts after an edit, given the counts of the lines it
+    /// spanned beforehand and the byte range of the inserted text. Only the edited lines are
+    /// counted again, as neither grapheme clusters nor words cross a line break.
+    fn update_counts(
+        &mut self,
+        cx: &mut EventContext,
+        counted: (usize, usize),
+        inserted: Range<usize>,
+    ) {
+        let (chars, words) = self.count_lines(cx, inserted);
+        self.char_count = (self.char_count + chars).saturating_sub(counted.0);
+        self.word_count = (self.word_count + words).saturating_sub(counted.1);
+    }
+
+    /// Shifts the extra carets and the ranges drawn over the text to account for an edit which
+    /// replaced the `removed` byte range with `inserted` bytes of text.
+    fn remap(&mut self, cx: &mut EventContext, removed: Range<usize>, inserted: usize) {
+        if !self.extra_cursors.is_empty() {
+            for (anchor, cursor) in self.extra_cursors.iter_mut() {
+                *anchor = remap_offset(*anchor, removed.clone(), inserted);
+                *cursor = remap_offset(*cursor, removed.clone(), inserted);
+            }
+            cx.text_context.set_extra_cursors(self.content_entity, self.extra_cursors.clone());
+        }
+        cx.text_context.remap_ranges(self.content_entity, removed, inserted);
+    }
+
+    /// Marks the text as changed, so that it is laid out and measured again.
+    fn text_changed(&mut self, cx: &mut EventContext) {
+        self.measured = None;
+        *self.text_cache.get_mut() = None;
+        cx.style.needs_text_layout.insert(self.content_entity, true).unwrap();
+    }
+
+    /// Refreshes the `empty` and `rtl` flags from the current contents of the buffer.
+    fn update_content_flags(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        (self.empty, self.rtl) = cx.text_context.with_buffer(self.content_entity, |buf| {
+            buf.shape_until_scroll();
+            let empty = buf.lines.len() <= 1 && buf.lines.iter().all(|line| line.text().is_empty());
+            let rtl = buf.layout_runs().next().map(|run| run.rtl).unwrap_or(false);
+            (empty, rtl)
+        });
+    }
+
+    /// Keeps focus within the textbox while it is being edited if Tab indents, so that Tab can only
+    /// move focus on once editing has ended, for example with Escape.
+    fn update_lock_focus(&mut self) {
+        let tab = KeyChord::new(Modifiers::empty(), Code::Tab);
+        self.lock_focus =
+            self.edit && matches!(self.key_bindings.get(&tab), Some(TextEvent::Indent));
+    }
+
+    fn notify_edit(&mut self, cx: &mut EventContext) {
+        if let Some(edited) = &mut self.batch {
+            *edited = true;
+            return;
+        }
+
+        self.is_dirty = true;
+
+        if let Some(callback) = self.on_edit.take() {
+            let text = self.output_text(cx);
+            (callback)(cx, text);
+
+            self.on_edit = Some(callback);
+        }
+
+        // The text is joined once per edit and shared with the other callbacks, and only copied
+        // for this callback if it asks for it.
+        if let Some(callback) = self.on_edit_ref.take() {
+            self.clone_text(cx);
+            let line_ending = self.line_ending();
+            let text_cache = &self.text_cache;
+            let text = || line_ending.apply(text_cache.borrow().clone().unwrap_or_default());
+            (callback)(cx, &text);
+
+            self.on_edit_ref = Some(callback);
+        }
+
+        // Restart the idle timer, only the most recent timer is allowed to invoke the callback.
+        if let Some((duration, _)) = &self.on_edit_debounced {
+            self.edit_generation += 1;
+            self.timer.schedule(cx, *duration, TextEvent::EditDebounced(self.edit_generation));
+        }
+
+        if self.on_highlight.is_some() {
+            self.highlight_generation += 1;
+            let event = TextEvent::Highlight(self.highlight_generation);
+            self.timer.schedule(cx, HIGHLIGHT_DELAY, event);
+        }
+
+        if self.on_spellcheck.is_some() {
+            self.spellcheck_generation += 1;
+            let event = TextEvent::Spellcheck(self.spellcheck_generation);
+            self.timer.schedule(cx, SPELLCHECK_DELAY, event);
+        }
+    }
+
+    /// Runs the highlighter over the current text and replaces the styled spans with its output.
+    fn highlight(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        if let Some(on_highlight) = self.on_highlight.clone() {
+            let text = self.clone_text(cx);
+            let spans = (on_highlight)(&text)
+                .into_iter()
+                .map(|(range, color)| {
+                    let color = CosmicColor::rgba(color.r(), color.g(), color.b(), color.a());
+                    (range, AttrsOwned::new(Attrs::new().color(color)))
+                })
+                .collect();
+            cx.text_context.set_spans(self.content_entity, spans);
+            cx.needs_redraw();
+        }
+    }
+
+    /// Runs the spellchecker over the current text and underlines the misspelled ranges it returns.
+    fn spellcheck(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        if let Some(on_spellcheck) = self.on_spellcheck.clone() {
+            let text = self.clone_text(cx);
+            cx.text_context.set_misspellings(self.content_entity, (on_spellcheck)(&text));
+            cx.needs_redraw();
+        }
+    }
+
+    pub fn move_cursor(&mut self, cx: &mut EventContext, movement: Movement, selection: bool) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        // Cosmic remembers the column of its own vertical moves, but forgets it whenever the cursor
+        // is placed with `set_editor_cursor`, so the column the caret set out from is kept here.
+        let goal_x = if let Movement::Line(Direction::Upstream | Direction::Downstream) = movement {
+            let cursor = self.cursor_offsets(cx).1;
+            match self.goal_x {
+                Some((offset, x)) if offset == cursor => Some(x),
+                _ => self.layout_caret(cx).map(|caret| caret.x),
+            }
+        } else {
+            None
+        };
+
+        self.move_cursor_by(cx, movement, selection);
+
+        if let Some(x) = goal_x {
+            self.move_to_x(cx, x);
+            self.goal_x = Some((self.cursor_offsets(cx).1, x));
+        }
+    }
+
+    /// Moves the cursor to the position on its line closest to `x`, in the untranslated physical
+    /// coordinates of `layout_caret`, keeping any selection anchor.
+    fn move_to_x(&mut self, cx: &mut EventContext, x: f32) {
+        let caret = match self.caret_rect(cx) {
+            Some(caret) => caret,
+            None => return,
+        };
+        let scale = cx.style.dpi_factor as f32;
+        if let Some(offset) =
+            self.offset_at(cx, x + self.transform.0 * scale, caret.y + caret.h / 2.0)
+        {
+            cx.text_context.with_editor(self.content_entity, |buf| {
+                set_editor_cursor(buf, offset_to_cursor(buf.buffer(), offset));
+            });
+        }
+    }
+
+    fn move_cursor_by(&mut self, cx: &mut EventContext, movement: Movement, selection: bool) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let base_rtl = self.base_direction.map(|direction| direction == BaseDirection::RightToLeft);
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            if selection {
+                if buf.select_opt().is_none() {
+                    buf.set_select_opt(Some(buf.cursor()));
+                }
+            } else {
+                buf.set_select_opt(None);
+            }
+
+            if let Movement::LineStart | Movement::LineEnd = movement {
+                let cursor = buf.cursor();
+                if let Some((range, rtl)) = run_at(buf.buffer(), cursor) {
+                    let home = matches!(movement, Movement::LineStart);
+                    let index = line_edge(range, rtl, base_rtl, home);
+                    set_editor_cursor(buf, Cursor::new(cursor.line, index));
+                }
+                return;
+            }
+
+            if let Movement::Paragraph(Direction::Upstream | Direction::Downstream) = movement {
+                let cursor = buf.cursor();
+                let last = buf.buffer().lines.len() - 1;
+                let cursor = if let Movement::Paragraph(Direction::Upstream) = movement {
+                    match (cursor.index, cursor.line) {
+                        (0, 0) => cursor,
+                        (0, line) => Cursor::new(line - 1, 0),
+                        (_, line) => Cursor::new(line, 0),
+                    }
+                } else if cursor.line < last {
+                    Cursor::new(cursor.line + 1, 0)
+                } else {
+                    Cursor::new(last, buf.buffer().lines[last].text().len())
+                };
+                set_editor_cursor(buf, cursor);
+                return;
+            }
+
+            if let Movement::SmartHome = movement {
+                let cursor = buf.cursor();
+                let line = buf.buffer().lines[cursor.line].text();
+                let indent = line.len() - line.trim_start().len();
+                let index = if cursor.index == indent { 0 } else { indent };
+                set_editor_cursor(buf, Cursor::new(cursor.line, index));
+                return;
+            }
+
+            buf.action(match movement {
+                Movement::Grapheme(Direction::Upstream) => Action::Previous,
+                Movement::Grapheme(Direction::Downstream) => Action::Next,
+                Movement::Grapheme(Direction::Left) => Action::Left,
+                Movement::Grapheme(Direction::Right) => Action::Right,
+                Movement::Word(Direction::Upstream) => Action::PreviousWord,
+                Movement::Word(Direction::Downstream) => Action::NextWord,
+                Movement::Word(Direction::Left) => Action::LeftWord,
+                Movement::Word(Direction::Right) => Action::RightWord,
+                Movement::Line(Direction::Upstream) => Action::Up,
+                Movement::Line(Direction::Downstream) => Action::Down,
+                Movement::Page(dir) => {
+                    let parent = self.content_entity.parent(cx.tree).unwrap();
+                    let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
+                    let sign = if let Direction::Upstream = dir { -1 } else { 1 };
+                    Action::Vertical(sign * parent_bounds.h as i32)
+                }
+                Movement::Body(Direction::Upstream) => Action::BufferStart,
+                Movement::Body(Direction::Downstream) => Action::BufferEnd,
+                _ => return,
+            });
+        });
+        cx.needs_redraw();
+    }
+
+    /// Returns the byte range of the lines covered by the selection, from the start of the first
+    /// line to the end of the last. A selection ending at the start of a line doesn't cover it.
+    fn selected_lines(&self, cx: &mut EventContext, text: &str) -> Range<usize> {
+        let selection = self.selection_range(cx);
+        let start = text[..selection.start].rfind('\n').map_or(0, |index| index + 1);
+        let last = if selection.end > start && text[..selection.end].ends_with('\n') {
+            selection.end - 1
+        } else {
+            selection.end
+        };
+        let end = text[last..].find('\n').map_or(text.len(), |index| last + index);
+        start..end
+    }
+
+    /// Edits each line covered by the selection as a single replacement. For each line `edit`
+    /// returns the byte range within the line to replace and its replacement, or `None` to leave
+    /// the line alone. The selection is kept on the same text. Returns whether any line changed.
+    fn edit_selected_lines(
+        &mut self,
+        cx: &mut EventContext,
+        mut edit: impl FnMut(&str) -> Option<(Range<usize>, String)>,
+    ) -> bool {
+        let text = self.clone_text(cx);
+        let lines = self.selected_lines(cx, &text);
+        let mut edits = Vec::new();
+        let mut line_start = lines.start;
+        for line in text[lines.clone()].split('\n') {
+            if let Some((range, replacement)) = edit(line) {
+                edits.push((line_start + range.start..line_start + range.end, replacement));
+            }
+            line_start += line.len() + 1;
+        }
+        if edits.is_empty() {
+            return false;
+        }
+
+        let mut replacement = String::new();
+        let mut offset = lines.start;
+        for (range, text_edit) in &edits {
+            replacement.push_str(&text[offset..range.start]);
+            replacement.push_str(text_edit);
+            offset = range.end;
+        }
+        replacement.push_str(&text[offset..lines.end]);
+
+        // Offsets before an edit stay put, offsets after it move by the change in length.
+        let remap = |offset: usize| {
+            let mut shift = 0isize;
+            for (range, text_edit) in &edits {
+                if offset <= range.start {
+                    break;
+                } else if offset < range.end {
+                    return (range.start as isize + shift) as usize
+                        + (offset - range.start).min(text_edit.len());
+                }
+                shift += text_edit.len() as isize - range.len() as isize;
+            }
+            (offset as isize + shift) as usize
+        };
+        let (anchor, cursor) = self.cursor_offsets(cx);
+        self.replace_range(cx, lines, &replacement);
+        self.select_between(cx, remap(anchor), remap(cursor));
+        true
+    }
+
+    /// Returns the selection anchor and cursor byte offsets of the editor.
+    fn cursor_offsets(&self, cx: &mut EventContext) -> (usize, usize) {
+        if self.content_entity == Entity::null() {
+            return (0, 0);
+        }
+
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            let cursor = cursor_to_offset(buf.buffer(), buf.cursor());
+            let anchor =
+                buf.select_opt().map_or(cursor, |select| cursor_to_offset(buf.buffer(), select));
+            (anchor, cursor)
+        })
+    }
+
+    /// Runs `f` once for each caret, with that caret and its selection placed in the editor. The
+    /// other carets are shifted along with any edit `f` makes, and carets whose selections end up
+    /// overlapping are merged afterwards.
+    fn for_each_cursor(
+        &mut self,
+        cx: &mut EventContext,
+        mut f: impl FnMut(&mut Self, &mut EventContext),
+    ) {
+        if self.extra_cursors.is_empty() {
+            f(self, cx);
+            return;
+        }
+
+        // The primary caret goes last so that it is left in the editor.
+        let primary = self.cursor_offsets(cx);
+        self.extra_cursors.push(primary);
+        let count = self.extra_cursors.len();
+        for i in 0..count {
+            // `f` may have removed the other carets, for example by replacing the text.
+            let Some(&(anchor, cursor)) = self.extra_cursors.get(i) else {
+                return;
+            };
+            self.select_between(cx, anchor, cursor);
+            f(self, cx);
+
+            let offsets = self.cursor_offsets(cx);
+            if let Some(cursor) = self.extra_cursors.get_mut(i) {
+                *cursor = offsets;
+            }
+        }
+
+        self.merge_extra_cursors(cx);
+    }
+
+    /// Places the last of `extra_cursors` in the editor as the primary caret, merging carets whose
+    /// selections overlap.
+    fn merge_extra_cursors(&mut self, cx: &mut EventContext) {
+        let cursors = std::mem::take(&mut self.extra_cursors);
+        let primary = cursors.len() - 1;
+        let ((anchor, cursor), others) = merge_cursors(cursors, primary);
+        self.select_between(cx, anchor, cursor);
+        self.set_extra_cursors(cx, others);
+    }
+
+    /// Returns the number of carets before the one in the editor, in the order they appear in the
+    /// text. Only meaningful while `for_each_cursor` runs.
+    #[cfg(feature = "clipboard")]
+    fn cursor_rank(&self, cx: &mut EventContext) -> usize {
+        let (anchor, cursor) = self.cursor_offsets(cx);
+        let start = anchor.min(cursor);
+        self.extra_cursors.iter().filter(|(anchor, cursor)| (*anchor).min(*cursor) < start).count()
+    }
+
+    /// Returns the selected text of every caret in the order it appears in the text, joined by line
+    /// breaks, or `None` if nothing is selected.
+    #[cfg(feature = "clipboard")]
+    fn clone_selections(&self, cx: &mut EventContext) -> Option<String> {
+        if self.extra_cursors.is_empty() {
+            return self.clone_selected(cx).filter(|text| !text.is_empty());
+        }
+
+        let text = self.clone_text(cx);
+        let mut ranges = self
+            .extra_cursors
+            .iter()
+            .copied()
+            .chain([self.cursor_offsets(cx)])
+            .map(|(anchor, cursor)| anchor.min(cursor)..anchor.max(cursor))
+            .filter(|range| !range.is_empty())
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+        let selections = ranges.into_iter().map(|range| &text[range]).collect::<Vec<_>>();
+        (!selections.is_empty()).then(|| selections.join("\n"))
+    }
+
+    /// Removes the given byte ranges, which may overlap, keeping every caret on the same text.
+    #[cfg(feature = "clipboard")]
+    fn delete_ranges(&mut self, cx: &mut EventContext, mut ranges: Vec<Range<usize>>) {
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        let primary = self.cursor_offsets(cx);
+        self.extra_cursors.push(primary);
+        for range in merged.into_iter().rev() {
+            self.select_range(cx, range);
+            self.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+        }
+        self.merge_extra_cursors(cx);
+    }
+
+    /// Adds a caret on the line above the topmost caret, or below the bottommost one.
+    pub fn add_cursor(&mut self, cx: &mut EventContext, direction: Direction) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let entity = self.content_entity;
+        let primary = self.cursor_offsets(cx);
+        let carets = self.extra_cursors.iter().map(|(_, cursor)| *cursor).chain([primary.1]);
+        let (from, action) = match direction {
+            Direction::Upstream => (carets.min().unwrap(), Action::Up),
+            Direction::Downstream => (carets.max().unwrap(), Action::Down),
+            _ => return,
+        };
+
+        let offset = cx.text_context.with_editor(entity, |buf| {
+            set_editor_cursor(buf, offset_to_cursor(buf.buffer(), from));
+            buf.set_select_opt(None);
+            buf.action(action);
+            cursor_to_offset(buf.buffer(), buf.cursor())
+        });
+        self.select_between(cx, primary.0, primary.1);
+
+        // On the first or last line there is nowhere to add a caret.
+        if offset != from
+            && offset != primary.1
+            && self.extra_cursors.iter().all(|(_, cursor)| *cursor != offset)
+        {
+            let mut cursors = self.extra_cursors.clone();
+            cursors.push((offset, offset));
+            self.set_extra_cursors(cx, cursors);
+        }
+    }
+
+    /// Sets the carets in addition to the one in the editor, as pairs of selection anchor and caret
+    /// byte offsets, and has them drawn.
+    fn set_extra_cursors(&mut self, cx: &mut EventContext, cursors: Vec<(usize, usize)>) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        cx.text_context.set_extra_cursors(self.content_entity, cursors.clone());
+        self.extra_cursors = cursors;
+        cx.needs_redraw();
+    }
+
+    /// Removes all carets but the one in the editor.
+    fn clear_extra_cursors(&mut self, cx: &mut EventContext) {
+        self.set_extra_cursors(cx, Vec::new());
+    }
+
+    /// Selects the given byte range of the text returned by `clone_text`, placing the cursor at the
+    /// end of the range.
+    pub fn select_range(&mut self, cx: &mut EventContext, range: Range<usize>) {
+        self.select_between(cx, range.start, range.end);
+    }
+
+    /// Selects the text between the `anchor` and `cursor` byte offsets, placing the cursor at
+    /// `cursor`.
+    fn select_between(&mut self, cx: &mut EventContext, anchor: usize, cursor: usize) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            let anchor = offset_to_cursor(buf.buffer(), anchor);
+            let cursor = offset_to_cursor(buf.buffer(), cursor);
+            set_editor_cursor(buf, cursor);
+            buf.set_select_opt(if anchor == cursor { None } else { Some(anchor) });
+        });
+        cx.needs_redraw();
+    }
+
+    pub fn select_all(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        self.clear_extra_cursors(cx);
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.action(Action::BufferStart);
+            buf.set_select_opt(Some(buf.cursor()));
+            buf.action(Action::BufferEnd);
+        });
+        cx.needs_redraw();
+    }
+
+    pub fn select_word(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.action(Action::PreviousWord);
+            buf.set_select_opt(Some(buf.cursor()));
+            buf.action(Action::NextWord);
+        });
+        self.selection_mode = SelectionMode::Word;
+        self.selection_anchor = self.selection_range(cx);
+        self.move_selection = None;
+        cx.needs_redraw();
+    }
+
+    pub fn select_paragraph(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.action(Action::ParagraphStart);
+            buf.set_select_opt(Some(buf.cursor()));
+            buf.action(Action::ParagraphEnd);
+        });
+        self.selection_mode = SelectionMode::Paragraph;
+        self.selection_anchor = self.selection_range(cx);
+        self.move_selection = None;
+        cx.needs_redraw();
+    }
+
+    pub fn deselect(&mut self, cx: &mut EventContext) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        self.clear_extra_cursors(cx);
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.set_select_opt(None);
+        });
+        cx.needs_redraw();
+    }
+
+    /// These input coordinates should be physical coordinates, i.e. what the mouse events provide.
+    /// The output text coordinates will also be physical, but relative to the top of the text
+    /// glyphs, appropriate for passage to cosmic.
+    pub fn coordinates_global_to_text(&self, cx: &EventContext, x: f32, y: f32) -> (f32, f32) {
+        let parent = self.content_entity.parent(cx.tree).unwrap();
+        let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
+
+        let x = x - self.transform.0 * cx.style.dpi_factor as f32 - parent_bounds.x;
+        let y = y - self.transform.1 * cx.style.dpi_factor as f32 - parent_bounds.y;
+        (x, y)
+    }
+
+    /// Like `coordinates_global_to_text`, but also undoes the horizontal offset of the line at `y`
+    /// when the text is centered or right-aligned, and the widened spaces of the line when it is
+    /// justified or has tabs advanced to tab stops, so that the result can be used to hit-test the
+    /// buffer.
+    fn coordinates_global_to_line(&self, cx: &mut EventContext, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = self.coordinates_global_to_text(cx, x, y);
+        let justify = self.text_align.justify();
+        let spacing = cx.text_context.spacing(self.content_entity);
+        if justify == 0.0 && spacing == Spacing::default() {
+            return (x, y);
+        }
+
+        let width = cx.cache.bounds.get(self.content_entity).map_or(0.0, |bounds| bounds.w);
+        let x = cx.text_context.with_buffer(self.content_entity, |buf| {
+            let metrics = buf.metrics();
+            // Points above or below the text hit the first or last line, as in `Buffer::hit`.
+            let mut line = None;
+            for run in buf.layout_runs() {
+                let bottom = (run.line_y - metrics.font_size + metrics.line_height) as f32;
+                let spaces = widened_spaces(buf, &run, spacing);
+                line = Some((widened_width(&run, &spaces), spaces));
+                if y < bottom {
+                    break;
+                }
+            }
+            match line {
+                Some((line_w, spaces)) => unwiden_x(&spaces, x - (width - line_w) * justify),
+                None => x,
+            }
+        });
+        (x, y)
+    }
+
+    /// Returns the bounds of the grapheme cluster starting at a byte offset into the text in
+    /// physical window coordinates, taking scrolling into account, or `None` if the offset is not
+    /// the start of a character. Useful for anchoring markers or popups to a specific token.
+    pub fn rect_for_offset(&self, cx: &mut EventContext, offset: usize) -> Option<BoundingBox> {
+        if self.content_entity == Entity::null() {
+            return None;
+        }
+
+        let text = self.clone_text(cx);
+        if !text.is_char_boundary(offset) {
+            return None;
+        }
+        let end = offset + text[offset..].graphemes(true).next()?.len();
+        let bounds = *cx.cache.bounds.get(self.content_entity)?;
+        let justify = self.text_align.justify();
+        let scale = cx.style.dpi_factor as f32;
+        let (tx, ty) = self.transform;
+        cx.text_context
+            .layout_range(
+                self.content_entity,
+                offset..end,
+                (bounds.x + bounds.w * justify, bounds.y),
+                (justify, 0.),
+            )
+            .into_iter()
+            .next()
+            .map(|(x, y, w, h)| BoundingBox { x: x + tx * scale, y: y + ty * scale, w, h })
+    }
+
+    /// Returns the byte offset into the text closest to the given window-global physical
+    /// coordinates, without moving the cursor. Useful for placing annotations or tooltips over the
+    /// text under the mouse.
+    pub fn offset_at_point(&self, cx: &mut EventContext, x: f32, y: f32) -> usize {
+        self.offset_at(cx, x, y).unwrap_or_default()
+    }
+
+    /// Returns the byte offset into the text closest to the given window-global physical
+    /// coordinates.
+    fn offset_at(&self, cx: &mut EventContext, x: f32, y: f32) -> Option<usize> {
+        if self.content_entity == Entity::null() {
+            return None;
+        }
+
+        let (x, y) = self.coordinates_global_to_line(cx, x, y);
+        cx.text_context.with_buffer(self.content_entity, |buf| {
+            buf.hit(x as i32, y as i32).map(|cursor| cursor_to_offset(buf, cursor))
+        })
+    }
+
+    /// This function takes window-global physical coordinates.
+    pub fn hit(&mut self, cx: &mut EventContext, x: f32, y: f32) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        self.clear_extra_cursors(cx);
+        let (x, y) = self.coordinates_global_to_line(cx, x, y);
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.action(Action::Click { x: x as i32, y: y as i32 })
+        });
+        self.selection_mode = SelectionMode::Char;
+        cx.needs_redraw();
+    }
+
+    /// This function takes window-global physical coordinates.
+    pub fn drag(&mut self, cx: &mut EventContext, x: f32, y: f32) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let (x, y) = self.coordinates_global_to_line(cx, x, y);
+        cx.text_context.with_editor(self.content_entity, |buf| {
+            buf.action(Action::Drag { x: x as i32, y: y as i32 })
+        });
+
+        // After a double or triple click the selection grows in whole words or paragraphs, keeping
+        // the initially selected range selected.
+        if self.selection_mode != SelectionMode::Char {
+            let offset = cx.text_context.with_editor(self.content_entity, |buf| {
+                cursor_to_offset(buf.buffer(), buf.cursor())
+            });
+            let text = self.clone_text(cx);
+            let (start, end) = match self.selection_mode {
+                SelectionMode::Paragraph => {
+                    let paragraph = paragraph_range_at(&text, offset);
+                    (paragraph.start, paragraph.end)
+                }
+                _ => (
+                    word_range_at(&text, offset).start,
+                    word_range_at(&text, offset.saturating_sub(1)).end,
+                ),
+            };
+
+            let anchor = self.selection_anchor.clone();
+            if start < anchor.start {
+                self.select_between(cx, anchor.end, start);
+            } else if end > anchor.end {
+                self.select_between(cx, anchor.start, end);
+            } else {
+                self.select_between(cx, anchor.start, anchor.end);
+            }
+        }
+
+        cx.needs_redraw();
+    }
+
+    /// Moves the text in the `range` byte range to `offset`, or copies it there if `copy` is true,
+    /// and selects it at its new position.
+    fn move_text(&mut self, cx: &mut EventContext, range: Range<usize>, offset: usize, copy: bool) {
+        // Dropping the text onto itself leaves it where it is.
+        let onto_itself = if copy {
+            range.start < offset && offset < range.end
+        } else {
+            range.start <= offset && offset <= range.end
+        };
+        if onto_itself {
+            return;
+        }
+
+        let text = self.clone_text(cx)[range.clone()].to_owned();
+        let mut offset = offset;
+        if !copy {
+            self.select_range(cx, range.clone());
+            self.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+            if offset > range.end {
+                offset -= range.len();
+            }
+        }
+
+        self.select_range(cx, offset..offset);
+        self.insert_text(cx, &text);
+        self.select_range(cx, offset..offset + text.len());
+        self.notify_edit(cx);
+    }
+
+    /// This function takes window-global physical dimensions.
+    pub fn scroll(&mut self, cx: &mut EventContext, x: f32, y: f32) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let entity = self.content_entity;
+        let parent = cx.tree.get_parent(entity).unwrap();
+        let bounds = *cx.cache.bounds.get(entity).unwrap();
+        let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
+        let (mut tx, mut ty) = self.transform;
+        let scale = cx.style.dpi_factor as f32;
+        tx *= scale;
+        ty *= scale;
+        tx += x * self.scroll_sensitivity;
+        ty += y * self.scroll_sensitivity;
+        (tx, ty) = enforce_text_bounds(&bounds, &parent_bounds, (tx, ty));
+        // The cached bounds can lag behind the text after a large edit, so clamp against the
+        // measured height of the text as well to keep a fast fling from revealing empty space.
+        let content_height = self.measure(cx).1 * scale;
+        ty = ty.clamp(-(content_height - parent_bounds.h).max(0.0), 0.0);
+        self.transform = (tx / scale, ty / scale);
+        self.update_scrollbar(&bounds, &parent_bounds, ty);
+    }
+
+    /// Scrolls the content vertically to `value`, where 0 shows the top of the text and 1 shows
+    /// the bottom.
+    pub fn scroll_to(&mut self, cx: &mut EventContext, value: f32) {
+        if self.content_entity == Entity::null() {
+            return;
+        }
+
+        let entity = self.content_entity;
+        let parent = cx.tree.get_parent(entity).unwrap();
+        let bounds = *cx.cache.bounds.get(entity).unwrap();
+        let parent_bounds = *cx.cache.bounds.get(parent).unwrap();
+        let scale = cx.style.dpi_factor as f32;
+        let overflow = (bounds.h - parent_bounds.h).max(0.0);
+        let ty = -(overflow * value.clamp(0.0, 1.0)).round();
+        self.transform.1 = ty / scale;
+        self.update_scrollbar(&bounds, &parent_bounds, ty);
+    }
+
+    pub fn clone_selected(&self, cx: &mut EventContext) -> Option<String> {
+        if self.content_entity == Entity::null() {
+            return None;
+        }
+
+        cx.text_context.with_editor(self.content_entity, |buf| buf.copy_selection())
+    }
+
+    /// Returns the text with the line ending set by `Handle::line_ending`, or else the line ending
+    /// of the text the textbox was loaded with, as passed to callbacks and put on the clipboard.
+    pub fn output_text(&self, cx: &mut EventContext) -> String {
+        self.line_ending().apply(self.clone_text(cx))
+    }
+
+    fn line_ending(&self) -> LineEnding {
+        self.line_ending.unwrap_or(self.loaded_line_ending)
+    }
+
+    pub fn clone_text(&self, cx: &mut EventContext) -> String {
+        if self.content_entity == Entity::null() {
+            return String::new();
+        }
+
+        if let Some(text) = &*self.text_cache.borrow() {
+            return text.clone();
+        }
+
+        let mut text = cx.text_context.with_buffer(self.content_entity, |buf| {
+            buf.lines.iter().map(|line| line.text()).collect::<Vec<_>>().join("\n")
+        });
+        // Text being composed by an input method is not part of the text yet.
+        if let Some(range) = &self.preedit {
+            text.replace_range(range.clone(), "");
+        }
+        *self.text_cache.borrow_mut() = Some(text.clone());
+        text
+    }
+
+    /// Calls `f` with the layout runs of the laid out text, for example to position custom overlays
+    /// over the text. Positions are physical and relative to the top-left of the text content.
+    pub fn with_layout_runs<O>(
+        &self,
+        cx: &mut EventContext,
+        f: impl FnOnce(LayoutRunIter) -> O,
+    ) -> O {
+        // Without content there is no text, so `f` is given the runs of an empty buffer, which is
+        // removed again so that it isn't shared with other data without content.
+        if self.content_entity == Entity::null() {
+            let output = cx.text_context.with_buffer(Entity::null(), |buf| f(buf.layout_runs()));
+            cx.text_context.clear_buffer(Entity::null());
+            return output;
+        }
+
+        cx.text_context.with_buffer(self.content_entity, |buf| {
+            buf.shape_until_scroll();
+            f(buf.layout_runs())
+        })
+    }
+
+    /// Returns the start and end byte offsets of every occurrence of `needle` in the text returned
+    /// by `clone_text`.
+    pub fn find(
+        &self,
+        cx: &mut EventContext,
+        needle: &str,
+        case_sensitive: bool,
+    ) -> Vec<(usize, usize)> {
+        find_matches(&self.clone_text(cx), needle, case_sensitive)
+    }
+
+    /// Replaces every occurrence of `needle` in the text with `replacement`, and returns the number
+    /// of occurrences replaced.
+    pub fn replace_all(
+        &mut self,
+        cx: &mut EventContext,
+        needle: &str,
+        replacement: &str,
+        case_sensitive: bool,
+    ) -> usize {
+        let matches = self.find(cx, needle, case_sensitive);
+        let replacements =
+            matches.iter().map(|(start, end)| (*start..*end, replacement)).collect::<Vec<_>>();
+        self.replace_ranges(cx, &replacements);
+
+        matches.len()
+    }
+
+    /// Replaces the given byte ranges, which must be in order and not overlap, with their
+    /// replacements in a single edit spanning from the start of the first range to the end of the
+    /// last.
+    fn replace_ranges<S: AsRef<str>>(
+        &mut self,
+        cx: &mut EventContext,
+        replacements: &[(Range<usize>, S)],
+    ) {
+        if replacements.is_empty() {
+            return;
+        }
+
+        let text = self.clone_text(cx);
+        let span = replacements[0].0.start..replacements[replacements.len() - 1].0.end;
+        let mut replaced = String::with_capacity(span.len());
+        let mut offset = span.start;
+        for (range, replacement) in replacements {
+            replaced.push_str(&text[offset..range.start]);
+            replaced.push_str(replacement.as_ref());
+            offset = range.end;
+        }
+        self.replace_range(cx, span, &replaced);
+    }
+
+    /// Returns the start and end byte offsets of every match of the regular expression `pattern`
+    /// in the text returned by `clone_text`. Each match is followed by the offsets of its capture
+    /// groups, or `None` for groups which didn't take part in the match.
+    #[cfg(feature = "regex")]
+    pub fn find_regex(
+        &self,
+        cx: &mut EventContext,
+        pattern: &str,
+    ) -> Result<Vec<Vec<Option<(usize, usize)>>>, regex::Error> {
+        let pattern = regex::Regex::new(pattern)?;
+        Ok(find_regex_matches(&self.clone_text(cx), &pattern))
+    }
+
+    /// Replaces every match of the regular expression `pattern` in the text with `replacement`, in
+    /// which references such as `$1` or `${name}` are replaced by the text of the capture groups.
+    /// Returns the number of matches replaced.
+    #[cfg(feature = "regex")]
+    pub fn replace_regex(
+        &mut self,
+        cx: &mut EventContext,
+        pattern: &str,
+        replacement: &str,
+    ) -> Result<usize, regex::Error> {
+        let pattern = regex::Regex::new(pattern)?;
+        let replacements = regex_replacements(&self.clone_text(cx), &pattern, replacement);
+        self.replace_ranges(cx, &replacements);
+
+        Ok(replacements.len())
+    }
+}
+
+#[derive(Clone)]
+pub enum TextEvent {
+    InsertText(String),
+    /// Replaces the text, unless it is being edited.
+    ResetText(String),
+    SetStyled(Vec<(Range<usize>, AttrsOwned)>),
+    HighlightMatches(Vec<(usize, usize)>),
+    SelectMatch(usize),
+    Clear,
+    DeleteText(Movement),
+    MoveCursor(Movement, bool),
+    AddCursorAbove,
+    AddCursorBelow,
+    TransposeChars,
+    /// Indents each line covered by the selection if it spans several lines, or otherwise inserts
+    /// an indent at the cursor.
+    Indent,
+    /// Removes one level of indentation from each line covered by the selection.
+    Outdent,
+    /// Comments out each line covered by the selection with the given token, such as `//`, or
+    /// uncomments them if they are all commented already. Blank lines are left alone. Not bound to
+    /// a key by default.
+    ToggleLineComment(String),
+    /// Inserts a copy of the lines covered by the selection below them, moving the caret and
+    /// selection onto the copy. Only applies to a multiline textbox.
+    DuplicateLine,
+    /// Swaps the lines covered by the selection with the line above them, keeping the caret and
+    /// selection on the moved lines. Only applies to a multiline textbox.
+    MoveLineUp,
+    /// Swaps the lines covered by the selection with the line below them, keeping the caret and
+    /// selection on the moved lines. Only applies to a multiline textbox.
+    MoveLineDown,
+    ToggleOverwrite,
+    TransformSelection(CaseTransform),
+    AcceptSuggestion(String),
+    /// Replaces a byte range of the text, whether or not it is being edited.
+    ReplaceRange(Range<usize>, String),
+    /// Replaces every occurrence of a string with another, optionally matching case.
+    ReplaceAll(String, String, bool),
+    /// Replaces every match of a regular expression, which may refer to capture groups with `$1`.
+    #[cfg(feature = "regex")]
+    ReplaceRegex(String, String),
+    /// Applies a sequence of events, laying out the caret and notifying `on_edit` once at the end
+    /// rather than after each edit.
+    Batch(Vec<TextEvent>),
+    SetPreedit(String, Option<(usize, usize)>),
+    /// Sets the dead key which is composed with the next character typed, or forgets it.
+    DeadKey(Option<char>),
+    SelectAll,
+    SelectWord,
+    SelectParagraph,
+    /// Grows the selection to the text between the nearest pair of brackets around it, or to the
+    /// brackets themselves if it already covers that text. Not bound to a key by default.
+    ExpandSelection,
+    //SetSelection(Selection),
+    StartEdit,
+    EndEdit,
+    Submit(SubmitReason),
+    Cancel,
+    SyncText,
+    Hit(f32, f32),
+    Drag(f32, f32),
+    Scroll(f32, f32),
+    SetScrollY(f32),
+    Copy,
+    Paste,
+    Cut,
+
+    // Helpers
+    SetOnEdit(Option<Arc<dyn Fn(&mut EventContext, String) + Send + Sync>>),
+    SetOnEditRef(Option<Arc<dyn Fn(&mut EventContext, &dyn Fn() -> String) + Send + Sync>>),
+    SetOnChange(Option<Arc<dyn Fn(&mut EventContext, EditDelta) + Send + Sync>>),
+    SetOnEditDebounced(Option<(Duration, Arc<dyn Fn(&mut EventContext, String) + Send + Sync>)>),
+    EditDebounced(usize),
+    SetOnHighlight(Option<Arc<dyn Fn(&str) -> Vec<(Range<usize>, Color)> + Send + Sync>>),
+    Highlight(usize),
+    SetOnSpellcheck(Option<Arc<dyn Fn(&str) -> Vec<Range<usize>> + Send + Sync>>),
+    Spellcheck(usize),
+    SetOnSubmit(Option<Arc<dyn Fn(&mut EventContext, String, SubmitReason) + Send + Sync>>),
+    SetOnCancel(Option<Arc<dyn Fn(&mut EventContext) + Send + Sync>>),
+    SetOnSelectionChange(
+        Option<Arc<dyn Fn(&mut EventContext, Option<(usize, usize)>) + Send + Sync>>,
+    ),
+    SetOnClipboardError(Option<Arc<dyn Fn(&mut EventContext, &dyn Error) + Send + Sync>>),
+    SetOnCopy(Option<Arc<dyn Fn(&mut EventContext, &str) -> ClipboardData + Send + Sync>>),
+    SetBaseDirection(Option<BaseDirection>),
+    SetNewlineReplacement(Option<char>),
+    SetSmartHome(bool),
+    SetAutoPairs(bool),
+    SetTextAlign(TextAlign),
+    SetLineEnding(LineEnding),
+    SetCutLineWhenEmpty(bool),
+    SetTrimOnSubmit(bool),
+    SetSubmitOnlyIfChanged(bool),
+    SetScrollToCaretOnReset(bool),
+    SetScrollSensitivity(f32),
+    SetSelectable(bool),
+    SetTabIndents(bool),
+    SetLineNumbers(bool),
+    SetWrapColumn(Option<usize>),
+    SetTabStop(Option<f32>),
+    SetAutoHeight(bool),
+    SetMaxRows(Option<usize>),
+    SetAutoGrow(usize, usize),
+    SetKeyBindings(KeyBindings),
+    OpenContextMenu(f32, f32),
+    DragOver(f32, f32),
+    DragLeave,
+    DropText(f32, f32, String),
+    EndDrag(f32, f32, bool),
+    CloseContextMenu,
+    InitContent(Entity, TextboxKind),
+    GeometryChanged,
+    ScaleFactorChanged,
+}
+
+impl Model for TextboxData {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|text_event, _| match text_event {
+            TextEvent::InsertText(text) => {
+                if self.edit {
+                    self.clear_preedit(cx);
+                    let text = match self.dead_key.take() {
+                        Some(dead_key) => compose_dead_key(dead_key, text),
+                        None => text.clone(),
+                    };
+                    // The text is edited with "\n" line endings whatever the line ending setting.
+                    let text = text.replace("\r\n", "\n");
+                    let text = if self.kind == TextboxKind::SingleLine {
+                        // Keep pasted text on a single line.
+                        replace_newlines(&text, self.newline_replacement)
+                    } else {
+                        text
+                    };
+                    if self.auto_pairs {
+                        self.for_each_cursor(cx, |data, cx| data.insert_paired(cx, &text));
+                    } else {
+                        self.for_each_cursor(cx, |data, cx| data.insert_text(cx, &text));
+                    }
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::ResetText(text) => {
+                // Don't discard what the user is typing, the bound value is restored when
+                // editing ends.
+                if self.edit {
+                    return;
+                }
+
+                self.reset_text(cx, text);
+                self.committed_text = self.clone_text(cx);
+                self.is_dirty = false;
+                if self.scroll_to_caret_on_reset {
+                    self.set_caret(cx);
+                } else {
+                    self.scroll(cx, 0.0, 0.0); // ensure_visible
+                    self.notify_selection_change(cx);
+                }
+                self.highlight(cx);
+                self.spellcheck(cx);
+                // Let assistive technology know the text was replaced.
+                let entity = cx.current();
+                cx.style.needs_access_update(entity);
+            }
+
+            TextEvent::SetStyled(spans) => {
+                cx.text_context.set_spans(self.content_entity, spans.clone());
+                cx.needs_redraw();
+            }
+
+            TextEvent::HighlightMatches(matches) => {
+                let ranges = matches.iter().map(|(start, end)| *start..*end).collect();
+                cx.text_context.set_match_highlights(self.content_entity, ranges);
+                cx.needs_redraw();
+            }
+
+            TextEvent::SelectMatch(index) => {
+                let matches = cx.text_context.match_highlights(self.content_entity);
+                if let Some(range) = matches.get(*index) {
+                    self.select_range(cx, range.clone());
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::Clear => {
+                let len = self.clone_text(cx).len();
+                self.reset_text(cx, "");
+                self.notify_change(cx, 0..len, "");
+                self.scroll(cx, 0.0, 0.0); // ensure_visible
+                self.notify_edit(cx);
+            }
+
+            TextEvent::DeleteText(movement) => {
+                if self.edit {
+                    self.for_each_cursor(cx, |data, cx| data.delete_text(cx, *movement));
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::MoveCursor(movement, selection) => {
+                if self.edit {
+                    let movement = match movement {
+                        Movement::LineStart if self.smart_home => Movement::SmartHome,
+                        movement => *movement,
+                    };
+                    let before = self.layout_caret(cx);
+                    self.for_each_cursor(cx, |data, cx| data.move_cursor(cx, movement, *selection));
+                    // Scroll along with a page movement so the caret stays at the same height in
+                    // the view, `set_caret` then keeps it within the view and the text bounds.
+                    if let (Movement::Page(_), Some(before), Some(after)) =
+                        (movement, before, self.layout_caret(cx))
+                    {
+                        self.transform.1 -= (after.y - before.y) / cx.style.dpi_factor as f32;
+                    }
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::AddCursorAbove => {
+                if self.edit {
+                    self.add_cursor(cx, Direction::Upstream);
+                    cx.needs_redraw();
+                }
+            }
+
+            TextEvent::AddCursorBelow => {
+                if self.edit {
+                    self.add_cursor(cx, Direction::Downstream);
+                    cx.needs_redraw();
+                }
+            }
+
+            TextEvent::StartEdit => {
+                if !cx.is_disabled() && !self.edit && self.selectable {
                     self.edit = true;
+                    // Keep the focus ring if focus arrived with the keyboard.
+                    let focus_visible =
+                        cx.focused() == cx.current()
+                            && cx.style.pseudo_classes.get(cx.current()).map_or(false, |classes| {
+                                classes.contains(PseudoClass::FOCUS_VISIBLE)
+                            });
+                    cx.focus_with_visibility(focus_visible);
+                    cx.capture();
+                    cx.set_checked(true);
+                    cx.emit(WindowEvent::SetImeAllowed(true));
+                    self.update_lock_focus();
+                }
+            }
+
+            TextEvent::EndEdit => {
+                self.clear_preedit(cx);
+                self.dead_key = None;
+                self.deselect(cx);
+                self.notify_selection_change(cx);
+                self.edit = false;
+                self.update_lock_focus();
+                self.context_menu_open = false;
+                cx.set_checked(false);
+                cx.release();
+                cx.emit(WindowEvent::SetImeAllowed(false));
+            }
+
+            TextEvent::Submit(reason) => {
+                if self.trim_on_submit {
+                    let text = self.clone_text(cx);
+                    let trimmed = trim_whitespace(&text);
+                    if trimmed != text {
+                        self.reset_text(cx, &trimmed);
+                        self.notify_change(cx, 0..text.len(), &trimmed);
+                        self.notify_edit(cx);
+                    }
+                }
+
+                let changed = self.clone_text(cx) != self.committed_text;
+                if !self.submit_only_if_changed || changed {
+                    if let Some(callback) = self.on_submit.take() {
+                        let text = self.output_text(cx);
+                        (callback)(cx, text, *reason);
+
+                        self.on_submit = Some(callback);
+                    }
+                }
+                self.committed_text = self.clone_text(cx);
+                self.is_dirty = false;
+                cx.emit(TextEvent::EndEdit);
+                // Emitted after any events sent by the callback, so that they are handled first.
+                cx.emit(TextEvent::SyncText);
+            }
+
+            TextEvent::Cancel => {
+                if let Some(callback) = self.on_cancel.take() {
+                    (callback)(cx);
+
+                    self.on_cancel = Some(callback);
+                }
+                cx.emit(TextEvent::EndEdit);
+            }
+
+            // Handled by the view, which has access to the bound value.
+            TextEvent::SyncText => {}
+
+            TextEvent::TransposeChars => {
+                if self.edit {
+                    self.transpose_chars(cx);
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::Indent => {
+                if self.edit {
+                    self.for_each_cursor(cx, |data, cx| {
+                        let multiline =
+                            data.clone_selected(cx).map_or(false, |text| text.contains('\n'));
+                        if multiline {
+                            data.edit_selected_lines(cx, |_| Some((0..0, INDENT.to_owned())));
+                        } else {
+                            data.insert_text(cx, INDENT);
+                        }
+                    });
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::ToggleLineComment(token) => {
+                if self.edit && !token.is_empty() {
+                    let text = self.clone_text(cx);
+                    let lines = self.selected_lines(cx, &text);
+                    let commented = text[lines]
+                        .split('\n')
+                        .filter(|line| !line.trim().is_empty())
+                        .all(|line| line.trim_start().starts_with(token.as_str()));
+                    let toggled = self.edit_selected_lines(cx, |line| {
+                        if line.trim().is_empty() {
+                            return None;
+                        }
+                        let indent = line.len() - line.trim_start().len();
+                        if commented {
+                            // Also remove the space which follows the token when commenting.
+                            let rest = &line[indent + token.len()..];
+                            let len = token.len() + usize::from(rest.starts_with(' '));
+                            Some((indent..indent + len, String::new()))
+                        } else {
+                            Some((indent..indent, format!("{} ", token)))
+                        }
+                    });
+                    if toggled {
+                        self.set_caret(cx);
+                        self.notify_edit(cx);
+                    }
+                }
+            }
+
+            TextEvent::DuplicateLine => {
+                if self.edit && self.kind != TextboxKind::SingleLine {
+                    let text = self.clone_text(cx);
+                    let lines = self.selected_lines(cx, &text);
+                    let copy = format!("\n{}", &text[lines.clone()]);
+                    let (anchor, cursor) = self.cursor_offsets(cx);
+                    self.insert_at(cx, lines.end, &copy);
+                    self.select_between(cx, anchor + copy.len(), cursor + copy.len());
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::MoveLineUp => {
+                if self.edit && self.kind != TextboxKind::SingleLine {
+                    let text = self.clone_text(cx);
+                    let lines = self.selected_lines(cx, &text);
+                    if lines.start > 0 {
+                        let above = text[..lines.start - 1].rfind('\n').map_or(0, |i| i + 1);
+                        let swapped =
+                            format!("{}\n{}", &text[lines.clone()], &text[above..lines.start - 1]);
+                        let (anchor, cursor) = self.cursor_offsets(cx);
+                        let shift = lines.start - above;
+                        self.replace_range(cx, above..lines.end, &swapped);
+                        self.select_between(cx, anchor - shift, cursor - shift);
+                        self.set_caret(cx);
+                        self.notify_edit(cx);
+                    }
+                }
+            }
+
+            TextEvent::MoveLineDown => {
+                if self.edit && self.kind != TextboxKind::SingleLine {
+                    let text = self.clone_text(cx);
+                    let lines = self.selected_lines(cx, &text);
+                    if lines.end < text.len() {
+                        let below = lines.end + 1;
+                        let end = text[below..].find('\n').map_or(text.len(), |i| below + i);
+                        let swapped = format!("{}\n{}", &text[below..end], &text[lines.clone()]);
+                        let (anchor, cursor) = self.cursor_offsets(cx);
+                        let shift = end - lines.end;
+                        self.replace_range(cx, lines.start..end, &swapped);
+                        self.select_between(cx, anchor + shift, cursor + shift);
+                        self.set_caret(cx);
+                        self.notify_edit(cx);
+                    }
+                }
+            }
+
+            TextEvent::Outdent => {
+                if self.edit {
+                    let outdented = self.edit_selected_lines(cx, |line| {
+                        let indent = if line.starts_with('\t') {
+                            1
+                        } else {
+                            (line.len() - line.trim_start_matches(' ').len()).min(INDENT.len())
+                        };
+                        (indent > 0).then(|| (0..indent, String::new()))
+                    });
+                    if outdented {
+                        self.set_caret(cx);
+                        self.notify_edit(cx);
+                    }
+                }
+            }
+
+            TextEvent::ToggleOverwrite => {
+                if self.edit {
+                    self.overwrite = !self.overwrite;
+                    cx.text_context.set_block_caret(self.content_entity, self.overwrite);
+                    cx.needs_redraw();
+                }
+            }
+
+            TextEvent::TransformSelection(transform) => {
+                if self.edit {
+                    self.transform_selection(cx, *transform);
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::AcceptSuggestion(suggestion) => {
+                if self.edit {
+                    self.accept_suggestion(cx, suggestion);
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::ReplaceRange(range, text) => {
+                self.replace_range(cx, range.clone(), text);
+                self.set_caret(cx);
+                self.notify_edit(cx);
+            }
+
+            TextEvent::ReplaceAll(needle, replacement, case_sensitive) => {
+                if self.replace_all(cx, needle, replacement, *case_sensitive) > 0 {
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            #[cfg(feature = "regex")]
+            TextEvent::ReplaceRegex(pattern, replacement) => {
+                // An invalid pattern replaces nothing.
+                if self.replace_regex(cx, pattern, replacement).unwrap_or(0) > 0 {
+                    self.set_caret(cx);
+                    self.notify_edit(cx);
+                }
+            }
+
+            TextEvent::Batch(events) => {
+                let outer = self.batch.replace(false);
+                for event in events {
+                    self.event(cx, &mut Event::new(event.clone()));
+                }
+                let edited = self.batch.take() == Some(true);
+                if let Some(outer) = outer {
+                    self.batch = Some(outer || edited);
+                } else {
+                    self.set_caret(cx);
+                    if edited {
+                        self.notify_edit(cx);
+                    }
+                }
+            }
+
+            TextEvent::DeadKey(dead_key) => {
+                if self.edit {
+                    self.dead_key = *dead_key;
+                }
+            }
+
+            TextEvent::SetPreedit(text, cursor) => {
+                if self.edit {
+                    self.set_preedit(cx, text, *cursor);
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::SelectAll => {
+                if self.selectable {
+                    self.select_all(cx);
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::SelectWord => {
+                if self.selectable {
+                    self.select_word(cx);
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::SelectParagraph => {
+                if self.selectable {
+                    self.select_paragraph(cx);
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::ExpandSelection => {
+                if self.selectable {
+                    let range = self.selection_range(cx);
+                    if let Some(range) = enclosing_brackets(&self.clone_text(cx), range) {
+                        self.select_range(cx, range);
+                        self.set_caret(cx);
+                    }
+                }
+            }
+
+            // TextEvent::SetSelection(selection) => {
+            //     self.selection = *selection;
+            // }
+            TextEvent::Hit(_, _) | TextEvent::Drag(_, _) if !self.selectable => {}
+
+            TextEvent::Hit(posx, posy) => {
+                // Pressing inside the selection may start dragging it, so the cursor is only moved
+                // once the mouse is released without dragging.
+                let selection = self.selection_range(cx);
+                let inside = self
+                    .offset_at(cx, *posx, *posy)
+                    .map(|offset| selection.start <= offset && offset < selection.end)
+                    .unwrap_or(false);
+                if inside {
+                    self.move_selection = Some(selection);
+                    self.moving_selection = false;
+                } else {
+                    self.hit(cx, *posx, *posy);
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::Drag(posx, posy) => {
+                if self.move_selection.is_some() {
+                    if !self.moving_selection {
+                        let (down_x, down_y) = cx.mouse.left.pos_down;
+                        let threshold = MOVE_THRESHOLD * cx.style.dpi_factor as f32;
+                        self.moving_selection = (*posx - down_x).abs() > threshold
+                            || (*posy - down_y).abs() > threshold;
+                    }
+
+                    if self.moving_selection {
+                        let offset = self.offset_at(cx, *posx, *posy);
+                        cx.text_context.set_drop_caret(self.content_entity, offset);
+                        cx.needs_redraw();
+                    }
+                } else {
+                    self.drag(cx, *posx, *posy);
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::EndDrag(posx, posy, copy) => {
+                if let Some(range) = self.move_selection.take() {
+                    if self.moving_selection {
+                        self.moving_selection = false;
+                        cx.text_context.set_drop_caret(self.content_entity, None);
+                        if let Some(offset) = self.offset_at(cx, *posx, *posy) {
+                            self.move_text(cx, range, offset, *copy);
+                        }
+                    } else {
+                        // The selection was clicked rather than dragged.
+                        let (down_x, down_y) = cx.mouse.left.pos_down;
+                        self.hit(cx, down_x, down_y);
+                    }
+                    self.set_caret(cx);
+                }
+            }
+
+            TextEvent::Scroll(x, y) => {
+                self.scroll(cx, *x, *y);
+            }
+
+            TextEvent::SetScrollY(value) => {
+                self.scroll_to(cx, *value);
+            }
+
+            TextEvent::Copy =>
+            {
+                #[cfg(feature = "clipboard")]
+                if self.edit {
+                    if let Some(selected_text) = self.clone_selections(cx) {
+                        let _ = cx.set_primary_selection(selected_text.clone());
+                        self.copy_to_clipboard(cx, selected_text);
+                    }
+                }
+            }
+
+            TextEvent::Paste =>
+            {
+                #[cfg(feature = "clipboard")]
+                if self.edit {
+                    match cx.get_clipboard() {
+                        Ok(text) => self.paste(cx, text),
+                        Err(error) => self.clipboard_error(cx, error),
+                    }
+                }
+            }
+
+            TextEvent::Cut => {
+                #[cfg(feature = "clipboard")]
+                if self.edit {
+                    // The text is only removed once it is safely on the clipboard.
+                    if let Some(selected_text) = self.clone_selections(cx) {
+                        let _ = cx.set_primary_selection(selected_text.clone());
+                        if self.copy_to_clipboard(cx, selected_text) {
+                            self.for_each_cursor(cx, |data, cx| {
+                                if !data.selection_range(cx).is_empty() {
+                                    data.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+                                }
+                            });
+                            self.notify_edit(cx);
+                        }
+                    } else if self.cut_line_when_empty {
+                        // Carets on the same line cut it once.
+                        let mut lines = Vec::new();
+                        self.for_each_cursor(cx, |data, cx| lines.extend(data.line_to_cut(cx)));
+                        lines.sort_by_key(|(range, _)| range.start);
+                        lines.dedup_by_key(|(range, _)| range.start);
+                        let text = lines.iter().map(|(_, line)| line.as_str()).collect::<String>();
+                        if !lines.is_empty() && self.copy_to_clipboard(cx, text) {
+                            self.delete_ranges(
+                                cx,
+                                lines.into_iter().map(|(range, _)| range).collect(),
+                            );
+                            self.set_caret(cx);
+                            self.notify_edit(cx);
+                        }
+                    }
+                }
+            }
+
+            TextEvent::OpenContextMenu(x, y) => {
+                self.has_selection = !self.selection_range(cx).is_empty();
+                #[cfg(feature = "clipboard")]
+                {
+                    self.can_paste =
+                        cx.get_clipboard().map(|text| !text.is_empty()).unwrap_or(false);
+                }
+                self.context_menu_position = (*x, *y);
+                self.context_menu_open = true;
+            }
+
+            TextEvent::CloseContextMenu => {
+                self.context_menu_open = false;
+            }
+
+            TextEvent::DragOver(x, y) => {
+                let offset = self.offset_at(cx, *x, *y);
+                cx.text_context.set_drop_caret(self.content_entity, offset);
+                cx.needs_redraw();
+            }
+
+            TextEvent::DragLeave => {
+                cx.text_context.set_drop_caret(self.content_entity, None);
+                cx.needs_redraw();
+            }
+
+            TextEvent::DropText(x, y, text) => {
+                cx.text_context.set_drop_caret(self.content_entity, None);
+                if let Some(offset) = self.offset_at(cx, *x, *y) {
+                    self.select_range(cx, offset..offset);
+                }
+                cx.emit(TextEvent::InsertText(text.clone()));
+            }
+
+            TextEvent::SetOnEdit(on_edit) => {
+                self.on_edit = on_edit.clone();
+            }
+
+            TextEvent::SetOnEditRef(on_edit_ref) => {
+                self.on_edit_ref = on_edit_ref.clone();
+            }
+
+            TextEvent::SetOnChange(on_change) => {
+                self.on_change = on_change.clone();
+            }
+
+            TextEvent::SetOnEditDebounced(on_edit_debounced) => {
+                self.on_edit_debounced = on_edit_debounced.clone();
+            }
+
+            TextEvent::SetOnHighlight(on_highlight) => {
+                self.on_highlight = on_highlight.clone();
+                self.highlight(cx);
+            }
+
+            TextEvent::Highlight(generation) => {
+                if *generation == self.highlight_generation {
+                    self.highlight(cx);
+                }
+            }
+
+            TextEvent::SetOnSpellcheck(on_spellcheck) => {
+                self.on_spellcheck = on_spellcheck.clone();
+                cx.text_context.set_misspellings(self.content_entity, Vec::new());
+                self.spellcheck(cx);
+            }
+
+            TextEvent::Spellcheck(generation) => {
+                if *generation == self.spellcheck_generation {
+                    self.spellcheck(cx);
+                }
+            }
+
+            TextEvent::EditDebounced(generation) => {
+                if *generation == self.edit_generation {
+                    if let Some((duration, callback)) = self.on_edit_debounced.take() {
+                        let text = self.output_text(cx);
+                        (callback)(cx, text);
+
+                        self.on_edit_debounced = Some((duration, callback));
+                    }
+                }
+            }
+
+            TextEvent::InitContent(content, kind) => {
+                self.content_entity = *content;
+                *self.text_cache.get_mut() = None;
+                self.kind = *kind;
+                self.key_bindings = KeyBindings::new(*kind != TextboxKind::SingleLine);
+                self.committed_text = self.clone_text(cx);
+                self.count_all(cx);
+                self.update_content_flags(cx);
+            }
+
+            // The text is measured again in case a style change resized it.
+            TextEvent::GeometryChanged => {
+                self.measured = None;
+                self.set_caret(cx);
+            }
+
+            TextEvent::ScaleFactorChanged => {
+                if self.content_entity != Entity::null() {
+                    self.text_changed(cx);
+                }
+                self.set_caret(cx);
+            }
+
+            TextEvent::SetBaseDirection(direction) => {
+                self.base_direction = *direction;
+            }
+
+            TextEvent::SetNewlineReplacement(replacement) => {
+                self.newline_replacement = *replacement;
+            }
+
+            TextEvent::SetSmartHome(flag) => {
+                self.smart_home = *flag;
+            }
+
+            TextEvent::SetAutoPairs(flag) => {
+                self.auto_pairs = *flag;
+            }
+
+            TextEvent::SetTextAlign(align) => {
+                self.text_align = *align;
+                cx.text_context.set_justified(self.content_entity, *align == TextAlign::Justify);
+                self.set_caret(cx);
+            }
+
+            TextEvent::SetLineEnding(line_ending) => {
+                self.line_ending = Some(*line_ending);
+            }
+
+            TextEvent::SetCutLineWhenEmpty(flag) => {
+                self.cut_line_when_empty = *flag;
+            }
+
+            TextEvent::SetTrimOnSubmit(flag) => {
+                self.trim_on_submit = *flag;
+            }
+
+            TextEvent::SetSubmitOnlyIfChanged(flag) => {
+                self.submit_only_if_changed = *flag;
+            }
+
+            TextEvent::SetScrollToCaretOnReset(flag) => {
+                self.scroll_to_caret_on_reset = *flag;
+            }
+
+            TextEvent::SetScrollSensitivity(sensitivity) => {
+                self.scroll_sensitivity = *sensitivity;
+            }
+
+            TextEvent::SetTabIndents(flag) => {
+                let tab = KeyChord::new(Modifiers::empty(), Code::Tab);
+                let shift_tab = KeyChord::new(Modifiers::SHIFT, Code::Tab);
+                self.key_bindings = if *flag {
+                    self.key_bindings
+                        .clone()
+                        .bind(tab, TextEvent::Indent)
+                        .bind(shift_tab, TextEvent::Outdent)
+                } else {
+                    self.key_bindings.clone().unbind(tab).unbind(shift_tab)
+                };
+                self.update_lock_focus();
+            }
+
+            TextEvent::SetSelectable(flag) => {
+                self.selectable = *flag;
+                if !flag && self.edit {
+                    cx.emit(TextEvent::EndEdit);
+                }
+            }
+
+            TextEvent::SetLineNumbers(flag) => {
+                self.line_numbers = *flag;
+                self.set_caret(cx);
+            }
+
+            TextEvent::SetAutoHeight(flag) => {
+                self.auto_height = *flag;
+                self.update_height(cx);
+            }
+
+            TextEvent::SetMaxRows(max_rows) => {
+                self.max_rows = *max_rows;
+                self.update_height(cx);
+            }
+
+            TextEvent::SetAutoGrow(min_rows, max_rows) => {
+                self.auto_height = true;
+                self.min_rows = *min_rows;
+                self.max_rows = Some(*max_rows);
+                self.update_height(cx);
+            }
+
+            TextEvent::SetWrapColumn(column) => {
+                self.wrap_column = *column;
+                self.set_caret(cx);
+            }
+
+            TextEvent::SetTabStop(tab_stop) => {
+                cx.text_context.set_tab_stop(self.content_entity, *tab_stop);
+                self.text_changed(cx);
+                self.set_caret(cx);
+            }
+
+            TextEvent::SetKeyBindings(key_bindings) => {
+                self.key_bindings = key_bindings.clone();
+                self.update_lock_focus();
+            }
+
+            TextEvent::SetOnSubmit(on_submit) => {
+                self.on_submit = on_submit.clone();
+            }
+
+            TextEvent::SetOnCancel(on_cancel) => {
+                self.on_cancel = on_cancel.clone();
+            }
+
+            TextEvent::SetOnSelectionChange(on_selection_change) => {
+                self.on_selection_change = on_selection_change.clone();
+            }
+
+            TextEvent::SetOnClipboardError(on_clipboard_error) => {
+                self.on_clipboard_error = on_clipboard_error.clone();
+            }
+
+            TextEvent::SetOnCopy(on_copy) => {
+                self.on_copy = on_copy.clone();
+            }
+        });
+    }
+}
+
+/// What a [`Textbox`] puts on the clipboard when text is copied or cut, returned by the `on_copy`
+/// callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardData {
+    /// Puts the given plain text on the system clipboard.
+    Text(String),
+    /// Leaves the system clipboard alone, because the application has already written to it,
+    /// for example with formatted flavors such as HTML or RTF alongside the plain text.
+    Handled,
+}
+
+/// The base direction of the text of a [`Textbox`], set with `Handle::base_direction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseDirection {
+    /// Home moves to the left edge of a line and End to the right edge.
+    LeftToRight,
+    /// Home moves to the right edge of a line and End to the left edge.
+    RightToLeft,
+}
+
+/// How the text of a [`Textbox`] was submitted, passed to the `on_submit_ex` callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitReason {
+    /// Enter was pressed in a single-line textbox.
+    Enter,
+    /// The textbox lost focus.
+    FocusLost,
+    /// The application emitted [`TextEvent::Submit`] itself.
+    Programmatic,
+}
+
+/// The line ending of the text a [`Textbox`] passes to callbacks and puts on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Lines end with `\n`.
+    Lf,
+    /// Lines end with `\r\n`, as expected by many Windows applications.
+    CrLf,
+}
+
+impl LineEnding {
+    /// Returns `CrLf` if the text contains a `\r\n` line ending, or `Lf` otherwise.
+    fn detect(text: &str) -> Self {
+        if text.contains("\r\n") {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// Converts text with lines separated by `\n` to this line ending.
+    fn apply(self, text: String) -> String {
+        match self {
+            LineEnding::Lf => text,
+            LineEnding::CrLf => text.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// A change to the text of a [`Textbox`], as passed to the `on_change` callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditDelta {
+    /// The byte range of the text before the change that was removed, which is empty if nothing
+    /// was removed.
+    pub range: Range<usize>,
+    /// The text inserted at the start of the range, which is empty if nothing was inserted.
+    pub text: String,
+}
+
+/// The horizontal alignment of the lines of text in a [`Textbox`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    /// Aligns each line to the left edge of the textbox.
+    Left,
+    /// Centers each line within the textbox.
+    Center,
+    /// Aligns each line to the right edge of the textbox.
+    Right,
+    /// Stretches each wrapped line to both edges of the textbox by widening the spaces between its
+    /// words. The last line of each paragraph is aligned to the left edge.
+    Justify,
+}
+
+impl TextAlign {
+    /// The fraction of the free space on a line placed before the text.
+    fn justify(self) -> f32 {
+        match self {
+            TextAlign::Left | TextAlign::Justify => 0.0,
+            TextAlign::Center => 0.5,
+            TextAlign::Right => 1.0,
+        }
+    }
+}
+
+/// A change of case applied to the selected text by [`TextEvent::TransformSelection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseTransform {
+    /// Converts the text to upper case.
+    Upper,
+    /// Converts the text to lower case.
+    Lower,
+    /// Capitalizes the first letter of each word and converts the rest to lower case.
+    Title,
+}
+
+/// Applies a unicode-aware change of case to `text`. The result may differ in length from `text`.
+fn transform_case(text: &str, transform: CaseTransform) -> String {
+    match transform {
+        CaseTransform::Upper => text.to_uppercase(),
+        CaseTransform::Lower => text.to_lowercase(),
+        CaseTransform::Title => text
+            .split_word_bounds()
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => {
+                        first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()
+                    }
+                    None => String::new(),
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Returns the largest character boundary of `text` no greater than `offset`.
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Returns the byte range of the word containing the character at `offset`, using unicode word
+/// boundaries.
+fn word_range_at(text: &str, offset: usize) -> Range<usize> {
+    text.split_word_bound_indices()
+        .map(|(start, word)| start..start + word.len())
+        .find(|range| offset < range.end)
+        .unwrap_or(text.len()..text.len())
+}
+
+/// Merges carets, given as pairs of selection anchor and caret byte offsets, whose selections overlap
+/// or which touch a caret without a selection. Returns the caret the one at index `primary` ended up
+/// in, which keeps its direction, and the others in the order they appear in the text.
+fn merge_cursors(
+    cursors: Vec<(usize, usize)>,
+    primary: usize,
+) -> ((usize, usize), Vec<(usize, usize)>) {
+    let mut cursors = cursors.into_iter().enumerate().collect::<Vec<_>>();
+    cursors.sort_by_key(|(_, (anchor, cursor))| (*anchor).min(*cursor));
+
+    // Each merged caret is its range, whether it selects backwards, and whether it is the primary.
+    let mut merged: Vec<(Range<usize>, bool, bool)> = Vec::new();
+    for (index, (anchor, cursor)) in cursors {
+        let range = anchor.min(cursor)..anchor.max(cursor);
+        let is_primary = index == primary;
+        if let Some((last, backwards, was_primary)) = merged.last_mut() {
+            let touches = range.start == last.end && (range.is_empty() || last.is_empty());
+            if range.start < last.end || touches {
+                last.end = last.end.max(range.end);
+                if is_primary {
+                    *backwards = cursor < anchor;
+                }
+                *was_primary |= is_primary;
+                continue;
+            }
+        }
+        merged.push((range, cursor < anchor, is_primary));
+    }
+
+    let mut primary = (0, 0);
+    let mut others = Vec::new();
+    for (range, backwards, is_primary) in merged {
+        let offsets = if backwards { (range.end, range.start) } else { (range.start, range.end) };
+        if is_primary {
+            primary = offsets;
+        } else {
+            others.push(offsets);
+        }
+    }
+    (primary, others)
+}
+
+/// Returns the byte range within its line of the layout run containing `cursor`, and whether the
+/// run is right-to-left.
+fn run_at(buffer: &Buffer, cursor: Cursor) -> Option<(Range<usize>, bool)> {
+    buffer
+        .layout_runs()
+        .filter(|run| run.line_i == cursor.line)
+        .map(|run| {
+            let start = run.glyphs.iter().map(|glyph| glyph.start).min();
+            let end = run.glyphs.iter().map(|glyph| glyph.end).max();
+            (start.unwrap_or_default()..end.unwrap_or_default(), run.rtl)
+        })
+        .find(|(range, _)| range.start <= cursor.index && cursor.index <= range.end)
+}
+
+/// Returns the index that Home (`home`) or End moves the cursor to within the visual line spanning
+/// `range`. Home moves to the left edge of the line for a left-to-right base direction and to the
+/// right edge for a right-to-left one, where `base_rtl` falls back to the direction of the line.
+fn line_edge(range: Range<usize>, line_rtl: bool, base_rtl: Option<bool>, home: bool) -> usize {
+    let base_rtl = base_rtl.unwrap_or(line_rtl);
+    // The left edge of a right-to-left line is its logical end.
+    let left = if line_rtl { range.end } else { range.start };
+    let right = if line_rtl { range.start } else { range.end };
+    if home == base_rtl {
+        right
+    } else {
+        left
+    }
+}
+
+/// Returns the combining mark corresponding to the accent produced by a dead key.
+fn combining_mark(dead_key: char) -> Option<char> {
+    Some(match dead_key {
+        '´' | '\'' => '\u{301}',
+        '`' => '\u{300}',
+        '^' => '\u{302}',
+        '~' => '\u{303}',
+        '¯' => '\u{304}',
+        '˘' => '\u{306}',
+        '˙' => '\u{307}',
+        '¨' | '"' => '\u{308}',
+        '°' | '˚' => '\u{30a}',
+        '˝' => '\u{30b}',
+        'ˇ' => '\u{30c}',
+        '¸' => '\u{327}',
+        '˛' => '\u{328}',
+        _ => return None,
+    })
+}
+
+/// Combines the accent of a dead key with the text typed after it, such as `´` and `e` into `é`.
+/// Pressing space or the dead key again produces the accent itself, and text which cannot be
+/// accented follows the accent.
+fn compose_dead_key(dead_key: char, text: &str) -> String {
+    let mut chars = text.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        _ => return format!("{}{}", dead_key, text),
+    };
+    if c == ' ' || c == dead_key {
+        return dead_key.to_string();
+    }
+
+    let composed = combining_mark(dead_key)
+        .map(|mark| [c, mark].into_iter().nfc().collect::<String>())
+        .filter(|composed| composed.chars().count() == 1);
+    composed.unwrap_or_else(|| format!("{}{}", dead_key, c))
+}
+
+/// Removes the whitespace at the end of each line of `text`, and at the start and end of the whole
+/// text.
+fn trim_whitespace(text: &str) -> String {
+    text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim().to_owned()
+}
+
+/// Replaces each line break in `text` with `replacement`, or removes it if there is no replacement.
+fn replace_newlines(text: &str, replacement: Option<char>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' | '\n' => {
+                // Treat "\r\n" as a single line break.
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                result.extend(replacement);
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Returns the byte range of the paragraph containing `offset`, excluding the trailing newline.
+fn paragraph_range_at(text: &str, offset: usize) -> Range<usize> {
+    let start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = text[offset..].find('\n').map(|i| offset + i).unwrap_or(text.len());
+    start..end
+}
+
+/// Returns the byte range to select to grow `range` to the nearest pair of balanced brackets
+/// around it. The text between the brackets is selected first, then the brackets themselves.
+fn enclosing_brackets(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    for (open, c) in text[..range.start].char_indices().rev() {
+        if !BRACKETS.iter().any(|(opening, _)| *opening == c) {
+            continue;
+        }
+
+        // Find the matching closing bracket, skipping over nested pairs.
+        let mut stack = Vec::new();
+        let mut close = None;
+        for (index, c) in text[open..].char_indices() {
+            if let Some((_, closing)) = BRACKETS.iter().find(|(opening, _)| *opening == c) {
+                stack.push(*closing);
+            } else if BRACKETS.iter().any(|(_, closing)| *closing == c) {
+                if stack.pop() != Some(c) {
+                    break;
+                }
+                if stack.is_empty() {
+                    close = Some(open + index);
+                    break;
+                }
+            }
+        }
+
+        match close {
+            Some(close) if close >= range.end => {
+                let inner = open + 1..close;
+                return Some(if inner == range { open..close + 1 } else { inner });
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Maps the key chords pressed while editing a [`Textbox`] to the [`TextEvent`]s they emit.
+///
+/// This type is part of the prelude.
+#[derive(Clone)]
+pub struct KeyBindings {
+    bindings: HashMap<KeyChord, TextEvent>,
+}
+
+impl KeyBindings {
+    /// Creates the default key bindings. Enter submits a single-line textbox and inserts a line
+    /// break into a multiline one, which is submitted with Ctrl+Enter instead, duplicates lines with
+    /// Ctrl+Shift+D and moves them with Alt+Up and Alt+Down. Ctrl+Up and Ctrl+Down move between
+    /// paragraphs. Shortcuts use Cmd in place of Ctrl on macOS, where paragraphs are moved between
+    /// with Option+Up and Option+Down instead, and lines are moved with Option+Cmd+[ and
+    /// Option+Cmd+] as in Xcode.
+    pub fn new(multiline: bool) -> Self {
+        Self::for_platform(multiline, cfg!(target_os = "macos"))
+    }
+
+    /// Creates the default key bindings of either macOS or the other platforms.
+    fn for_platform(multiline: bool, macos: bool) -> Self {
+        let ctrl = if macos { Modifiers::LOGO } else { Modifiers::CTRL };
+        let mut key_bindings = Self::empty()
+            .bind(KeyChord::new(ctrl, Code::KeyC), TextEvent::Copy)
+            .bind(KeyChord::new(ctrl, Code::KeyV), TextEvent::Paste)
+            // Pasted text is always plain, so paste-and-match-style is the same as pasting.
+            .bind(KeyChord::new(ctrl | Modifiers::SHIFT, Code::KeyV), TextEvent::Paste)
+            .bind(KeyChord::new(ctrl, Code::KeyX), TextEvent::Cut)
+            .bind(KeyChord::new(ctrl, Code::KeyK), TextEvent::DeleteText(Movement::LineEnd))
+            .bind(KeyChord::new(ctrl, Code::KeyT), TextEvent::TransposeChars)
+            .bind(KeyChord::new(Modifiers::empty(), Code::Insert), TextEvent::ToggleOverwrite);
+
+        // Holding Shift extends the selection, and otherwise has no effect.
+        for (shift, selection) in [(Modifiers::empty(), false), (Modifiers::SHIFT, true)] {
+            let bindings = [
+                (
+                    Code::ArrowLeft,
+                    Movement::Grapheme(Direction::Left),
+                    Movement::Word(Direction::Left),
+                ),
+                (
+                    Code::ArrowRight,
+                    Movement::Grapheme(Direction::Right),
+                    Movement::Word(Direction::Right),
+                ),
+                (
+                    Code::ArrowUp,
+                    Movement::Line(Direction::Upstream),
+                    Movement::Paragraph(Direction::Upstream),
+                ),
+                (
+                    Code::ArrowDown,
+                    Movement::Line(Direction::Downstream),
+                    Movement::Paragraph(Direction::Downstream),
+                ),
+                (Code::Home, Movement::LineStart, Movement::LineStart),
+                (Code::End, Movement::LineEnd, Movement::LineEnd),
+                (
+                    Code::PageUp,
+                    Movement::Page(Direction::Upstream),
+                    Movement::Body(Direction::Upstream),
+                ),
+                (
+                    Code::PageDown,
+                    Movement::Page(Direction::Downstream),
+                    Movement::Body(Direction::Downstream),
+                ),
+            ];
+
+            for (code, movement, ctrl_movement) in bindings {
+                key_bindings = key_bindings
+                    .bind(KeyChord::new(shift, code), TextEvent::MoveCursor(movement, selection))
+                    .bind(
+                        KeyChord::new(shift | ctrl, code),
+                        TextEvent::MoveCursor(ctrl_movement, selection),
+                    );
+            }
+
+            key_bindings = key_bindings
+                .bind(
+                    KeyChord::new(shift, Code::Backspace),
+                    TextEvent::DeleteText(Movement::Grapheme(Direction::Upstream)),
+                )
+                .bind(
+                    KeyChord::new(shift | ctrl, Code::Backspace),
+                    TextEvent::DeleteText(Movement::Word(Direction::Upstream)),
+                )
+                .bind(
+                    KeyChord::new(shift, Code::Delete),
+                    TextEvent::DeleteText(Movement::Grapheme(Direction::Downstream)),
+                )
+                .bind(
+                    KeyChord::new(shift | ctrl, Code::Delete),
+                    TextEvent::DeleteText(Movement::Word(Direction::Downstream)),
+                )
+                .bind(KeyChord::new(shift, Code::Escape), TextEvent::Cancel)
+                .bind(KeyChord::new(shift | ctrl, Code::KeyA), TextEvent::SelectAll);
+
+            // Shift+Enter does nothing in a single-line textbox rather than submitting it.
+            if multiline {
+                key_bindings = key_bindings.bind(
+                    KeyChord::new(shift, Code::Enter),
+                    TextEvent::InsertText("\n".to_owned()),
+                );
+            } else if shift.is_empty() {
+                key_bindings = key_bindings.bind(
+                    KeyChord::new(shift, Code::Enter),
+                    TextEvent::Submit(SubmitReason::Enter),
+                );
+            }
+
+            // On macOS, Option moves by word and Cmd moves to the ends of the line or text.
+            if macos {
+                let alt = Modifiers::ALT;
+                let bindings = [
+                    (alt, Code::ArrowLeft, Movement::Word(Direction::Left)),
+                    (alt, Code::ArrowRight, Movement::Word(Direction::Right)),
+                    (alt, Code::ArrowUp, Movement::Paragraph(Direction::Upstream)),
+                    (alt, Code::ArrowDown, Movement::Paragraph(Direction::Downstream)),
+                    (ctrl, Code::ArrowLeft, Movement::LineStart),
+                    (ctrl, Code::ArrowRight, Movement::LineEnd),
+                    (ctrl, Code::ArrowUp, Movement::Body(Direction::Upstream)),
+                    (ctrl, Code::ArrowDown, Movement::Body(Direction::Downstream)),
+                ];
+
+                for (modifier, code, movement) in bindings {
+                    key_bindings = key_bindings.bind(
+                        KeyChord::new(shift | modifier, code),
+                        TextEvent::MoveCursor(movement, selection),
+                    );
+                }
+
+                key_bindings = key_bindings
+                    .bind(
+                        KeyChord::new(shift | alt, Code::Backspace),
+                        TextEvent::DeleteText(Movement::Word(Direction::Upstream)),
+                    )
+                    .bind(
+                        KeyChord::new(shift | alt, Code::Delete),
+                        TextEvent::DeleteText(Movement::Word(Direction::Downstream)),
+                    )
+                    .bind(
+                        KeyChord::new(shift | ctrl, Code::Backspace),
+                        TextEvent::DeleteText(Movement::LineStart),
+                    );
+            }
+        }
+
+        if multiline {
+            // Option+Up and Option+Down already move between paragraphs on macOS, and
+            // Option+Cmd+Up and Option+Cmd+Down add cursors.
+            let (line_up, line_down) = if macos {
+                (
+                    KeyChord::new(ctrl | Modifiers::ALT, Code::BracketLeft),
+                    KeyChord::new(ctrl | Modifiers::ALT, Code::BracketRight),
+                )
+            } else {
+                (
+                    KeyChord::new(Modifiers::ALT, Code::ArrowUp),
+                    KeyChord::new(Modifiers::ALT, Code::ArrowDown),
+                )
+            };
+
+            key_bindings = key_bindings
+                .bind(KeyChord::new(ctrl, Code::Enter), TextEvent::Submit(SubmitReason::Enter))
+                .bind(KeyChord::new(ctrl | Modifiers::SHIFT, Code::KeyD), TextEvent::DuplicateLine)
+                .bind(line_up, TextEvent::MoveLineUp)
+                .bind(line_down, TextEvent::MoveLineDown)
+                .bind(
+                    KeyChord::new(ctrl | Modifiers::ALT, Code::ArrowUp),
+                    TextEvent::AddCursorAbove,
+                )
+                .bind(
+                    KeyChord::new(ctrl | Modifiers::ALT, Code::ArrowDown),
+                    TextEvent::AddCursorBelow,
+                );
+        }
+
+        key_bindings
+    }
+
+    /// Creates key bindings without any key chords bound.
+    pub fn empty() -> Self {
+        Self { bindings: HashMap::new() }
+    }
+
+    /// Binds `chord` to `event`, replacing any event it was previously bound to.
+    pub fn bind(mut self, chord: KeyChord, event: TextEvent) -> Self {
+        self.bindings.insert(chord, event);
+
+        self
+    }
+
+    /// Removes the binding of `chord`.
+    pub fn unbind(mut self, chord: KeyChord) -> Self {
+        self.bindings.remove(&chord);
+
+        self
+    }
+
+    /// Returns the event bound to `chord`, if any.
+    pub fn get(&self, chord: &KeyChord) -> Option<&TextEvent> {
+        self.bindings.get(chord)
+    }
+}
+
+/// Emits events back to a textbox once their delay has elapsed, from a single thread which is
+/// started the first time an event is scheduled. Scheduling an event replaces a pending event of
+/// the same variant, so each edit moves the deadline along rather than starting another timer.
+#[derive(Clone, Default)]
+struct EditTimer {
+    shared: Arc<(Mutex<TimerState>, Condvar)>,
+}
+
+#[derive(Default)]
+struct TimerState {
+    pending: Vec<(Instant, TextEvent)>,
+    started: bool,
+    stopped: bool,
+}
+
+impl EditTimer {
+    /// Emits `event` back to the current entity after `delay` has elapsed, unless another event of
+    /// the same variant is scheduled before then.
+    fn schedule(&self, cx: &mut EventContext, delay: Duration, event: TextEvent) {
+        let (state, condvar) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        let variant = std::mem::discriminant(&event);
+        state.pending.retain(|(_, pending)| std::mem::discriminant(pending) != variant);
+        state.pending.push((Instant::now() + delay, event));
+        condvar.notify_one();
+
+        if !state.started {
+            state.started = true;
+            let shared = self.shared.clone();
+            cx.spawn(move |cx| run_timer(&shared, cx));
+        }
+    }
+}
+
+impl Drop for EditTimer {
+    fn drop(&mut self) {
+        // The thread holds the only other reference once the last textbox data sharing the timer
+        // is dropped.
+        let (state, condvar) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        if state.started && Arc::strong_count(&self.shared) <= 2 {
+            state.stopped = true;
+            condvar.notify_one();
+        }
+    }
+}
+
+fn run_timer(shared: &(Mutex<TimerState>, Condvar), cx: &mut ContextProxy) {
+    let (state, condvar) = shared;
+    let mut state = state.lock().unwrap();
+    while !state.stopped {
+        let now = Instant::now();
+        let Some(deadline) = state.pending.iter().map(|(deadline, _)| *deadline).min() else {
+            state = condvar.wait(state).unwrap();
+            continue;
+        };
+
+        if deadline > now {
+            state = condvar.wait_timeout(state, deadline - now).unwrap().0;
+            continue;
+        }
+
+        let (due, pending) = std::mem::take(&mut state.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+        state.pending = pending;
+        for (_, event) in due {
+            if cx.emit(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub struct Textbox<L: Lens> {
+    lens: L,
+    kind: TextboxKind,
+    content_entity: Entity,
+    select_all_on_focus: bool,
+    clear_button: Option<Entity>,
+    scrollbar: Option<Entity>,
+    context_menu: bool,
+    accept_drop: bool,
+    submit_on_blur: bool,
+    /// A view belonging to the textbox, such as the list of options of a combo box, which can be
+    /// pressed without ending the edit.
+    pub(crate) popup: Option<Entity>,
+}
+
+impl<L: Lens> Textbox<L> {
+    /// Returns true if `entity` is the scrollbar or its thumb.
+    fn in_scrollbar(&self, cx: &EventContext, entity: Entity) -> bool {
+        self.scrollbar.map_or(false, |scrollbar| {
+            entity == scrollbar || entity.parent(cx.tree) == Some(scrollbar)
+        })
+    }
+}
+
+/// The menu shown when a textbox is right-clicked.
+struct ContextMenu;
+
+impl View for ContextMenu {}
+
+/// An item of the context menu of a textbox, along with the event it emits to the textbox.
+struct ContextMenuItem {
+    action: fn() -> TextEvent,
+}
+
+impl View for ContextMenuItem {}
+
+/// The granularity with which a mouse drag extends the selection, determined by the click that
+/// started it.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum SelectionMode {
+    Char,
+    Word,
+    Paragraph,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TextboxKind {
+    SingleLine,
+    MultiLineUnwrapped,
+    MultiLineWrapped,
+}
+
+impl<L: Lens> Textbox<L>
+where
+    <L as Lens>::Target: Data + Clone + ToString,
+{
+    pub fn new(cx: &mut Context, lens: L) -> Handle<Self> {
+        Self::new_core(cx, lens, TextboxKind::SingleLine)
+    }
+
+    pub fn new_multiline(cx: &mut Context, lens: L, wrap: bool) -> Handle<Self> {
+        Self::new_core(
+            cx,
+            lens,
+            if wrap { TextboxKind::MultiLineWrapped } else { TextboxKind::MultiLineUnwrapped },
+        )
+    }
+
+    /// Creates a single-line textbox which writes each edit back to the bound data. Lenses are
+    /// read-only, so edits are written by emitting the [`Setter`] event returned by `setter`, for
+    /// example a variant of the enum generated by `#[derive(Setter)]` for the model.
+    ///
+    /// # Example
+    /// ```
+    /// # use vizia_core::prelude::*;
+    /// # let cx = &mut Context::default();
+    /// #[derive(Lens, Model, Setter)]
+    /// pub struct AppData {
+    ///     text: String,
+    /// }
+    ///
+    /// # AppData { text: String::new() }.build(cx);
+    /// Textbox::new_bound(cx, AppData::text, AppDataSetter::Text);
+    /// ```
+    pub fn new_bound<S, F>(cx: &mut Context, lens: L, setter: F) -> Handle<Self>
+    where
+        S: Setter + Send + 'static,
+        F: 'static + Fn(String) -> S + Send + Sync,
+    {
+        Self::new(cx, lens).on_edit(move |cx, text| cx.emit(setter(text)))
+    }
+
+    fn new_core(cx: &mut Context, lens: L, kind: TextboxKind) -> Handle<Self> {
+        let text_lens = lens.clone();
+        // TODO can this be simplified now that text doesn't live in TextboxData?
+        let textbox = Self {
+            lens: lens.clone(),
+            kind,
+            content_entity: Entity::null(),
+            select_all_on_focus: false,
+            clear_button: None,
+            scrollbar: None,
+            context_menu: true,
+            accept_drop: true,
+            submit_on_blur: false,
+            popup: None,
+        };
+        let result = textbox.build(cx, move |cx| {
+            Binding::new(cx, lens.clone(), |cx, text| {
+                let text_str = text.view(cx.data().unwrap(), |text| {
+                    text.map(|x| x.to_string()).unwrap_or_else(|| "".to_owned())
+                });
+                if let Some(text_data) = cx.data::<TextboxData>() {
+                    if !text_data.edit {
+                        let mut td = TextboxData {
+                            edit: text_data.edit,
+                            is_dirty: false,
+                            committed_text: String::new(),
+                            transform: text_data.transform,
+                            scroll_y: text_data.scroll_y,
+                            scroll_ratio: text_data.scroll_ratio,
+                            line_numbers: text_data.line_numbers,
+                            line_number_text: text_data.line_number_text.clone(),
+                            measured: None,
+                            text_cache: RefCell::new(None),
+                            extra_cursors: Vec::new(),
+                            wrap_column: text_data.wrap_column,
+                            ruler_x: text_data.ruler_x,
+                            auto_height: text_data.auto_height,
+                            min_rows: text_data.min_rows,
+                            max_rows: text_data.max_rows,
+                            on_edit: text_data.on_edit.clone(),
+                            on_edit_ref: text_data.on_edit_ref.clone(),
+                            on_change: text_data.on_change.clone(),
+                            on_edit_debounced: text_data.on_edit_debounced.clone(),
+                            edit_generation: text_data.edit_generation,
+                            on_highlight: text_data.on_highlight.clone(),
+                            highlight_generation: text_data.highlight_generation,
+                            on_spellcheck: text_data.on_spellcheck.clone(),
+                            spellcheck_generation: text_data.spellcheck_generation,
+                            timer: text_data.timer.clone(),
+                            content_entity: text_data.content_entity,
+                            kind: text_data.kind,
+                            selection_mode: SelectionMode::Char,
+                            selection_anchor: 0..0,
+                            move_selection: None,
+                            goal_x: None,
+                            moving_selection: false,
+                            batch: None,
+                            preedit: None,
+                            dead_key: None,
+                            base_direction: text_data.base_direction,
+                            newline_replacement: text_data.newline_replacement,
+                            smart_home: text_data.smart_home,
+                            auto_pairs: text_data.auto_pairs,
+                            text_align: text_data.text_align,
+                            line_ending: text_data.line_ending,
+                            loaded_line_ending: LineEnding::detect(&text_str),
+                            overwrite: text_data.overwrite,
+                            cut_line_when_empty: text_data.cut_line_when_empty,
+                            trim_on_submit: text_data.trim_on_submit,
+                            submit_only_if_changed: text_data.submit_only_if_changed,
+                            scroll_to_caret_on_reset: text_data.scroll_to_caret_on_reset,
+                            scroll_sensitivity: text_data.scroll_sensitivity,
+                            selectable: text_data.selectable,
+                            key_bindings: text_data.key_bindings.clone(),
+                            empty: text_str.is_empty(),
+                            rtl: text_data.rtl,
+                            char_count: text_str.graphemes(true).count(),
+                            word_count: text_str.unicode_words().count(),
+                            context_menu_open: false,
+                            lock_focus: false,
+                            context_menu_position: text_data.context_menu_position,
+                            has_selection: false,
+                            last_selection: text_data.last_selection,
+                            can_paste: false,
+                            on_submit: text_data.on_submit.clone(),
+                            on_cancel: text_data.on_cancel.clone(),
+                            on_selection_change: text_data.on_selection_change.clone(),
+                            on_clipboard_error: text_data.on_clipboard_error.clone(),
+                            on_copy: text_data.on_copy.clone(),
+                        };
+                        // Keep the caret and selection where they were, as near as the new text
+                        // allows.
+                        cx.text_context.with_editor(text_data.content_entity, |buf| {
+                            let cursor = cursor_to_offset(buf.buffer(), buf.cursor());
+                            let anchor = buf
+                                .select_opt()
+                                .map_or(cursor, |select| cursor_to_offset(buf.buffer(), select));
+                            buf.buffer_mut().set_text(&text_str, Attrs::new());
+                            let anchor = floor_char_boundary(&text_str, anchor);
+                            let cursor = floor_char_boundary(&text_str, cursor);
+                            let anchor = offset_to_cursor(buf.buffer(), anchor);
+                            let cursor = offset_to_cursor(buf.buffer(), cursor);
+                            set_editor_cursor(buf, cursor);
+                            buf.set_select_opt(if anchor == cursor { None } else { Some(anchor) });
+                        });
+                        td.committed_text = td.clone_text(&mut EventContext::new(cx));
+                        cx.text_context.clear_spans(td.content_entity);
+                        cx.text_context.set_match_highlights(td.content_entity, Vec::new());
+                        cx.text_context.set_misspellings(td.content_entity, Vec::new());
+                        if td.on_highlight.is_some() {
+                            let parent = cx.current().parent(&cx.tree).unwrap();
+                            cx.emit_to(parent, TextEvent::Highlight(td.highlight_generation));
+                        }
+                        if td.on_spellcheck.is_some() {
+                            let parent = cx.current().parent(&cx.tree).unwrap();
+                            cx.emit_to(parent, TextEvent::Spellcheck(td.spellcheck_generation));
+                        }
+                        let parent = cx.current().parent(&cx.tree).unwrap();
+                        cx.style.needs_access_update(parent);
+                        cx.with_current(parent, |cx| td.build(cx));
+                        // push an event into the queue to force an update because the textbox data
+                        // may have already been observed this update cycle
+                        cx.emit_to(cx.current(), ());
+                    }
+                } else {
+                    let mut td = TextboxData::new();
+                    td.loaded_line_ending = LineEnding::detect(&text_str);
+                    td.set_caret(&mut EventContext::new(cx));
+                    let parent = cx.current().parent(&cx.tree).unwrap();
+                    cx.with_current(parent, |cx| td.build(cx));
+                    cx.emit_to(cx.current(), ());
+                }
+            });
+            let text = lens.view(cx.data().unwrap(), |text| {
+                text.map(|x| x.to_string()).unwrap_or_else(|| "".to_owned())
+            });
+            TextboxContainer {}
+                .build(cx, move |cx| {
+                    let lbl = TextboxLabel {}
+                        .build(cx, |_| {})
+                        .hidden(true)
+                        .navigable(false)
+                        .hoverable(false)
+                        .class("textbox_content")
+                        .text(&text)
+                        .translate(TextboxData::transform)
+                        .on_geo_changed(|cx, _| cx.emit(TextEvent::GeometryChanged))
+                        .entity;
+
+                    cx.emit(TextEvent::InitContent(lbl, kind));
+                    cx.text_context.with_buffer(lbl, |buf| {
+                        buf.set_text(&text, Attrs::new());
+                    });
+                })
+                .hidden(true)
+                .navigable(false)
+                .hoverable(false)
+                .class("textbox_container");
+
+            // Tab is only kept from moving focus on while the textbox is being edited, as the focus
+            // lock lives in the tree, which can't be changed from an event handler.
+            Binding::new(cx, TextboxData::lock_focus, |cx, lock_focus| {
+                let textbox = cx.current().parent(&cx.tree).unwrap();
+                cx.tree.set_lock_focus_within(textbox, lock_focus.get(cx));
+            });
+
+            // The context menu is only built while it is open.
+            Binding::new(cx, TextboxData::context_menu_open, |cx, open| {
+                if open.get(cx) {
+                    build_context_menu(cx);
+                }
+            });
+        });
+
+        result
+            .toggle_class("context_menu_open", TextboxData::context_menu_open)
+            .class(match kind {
+                TextboxKind::SingleLine => "single_line",
+                TextboxKind::MultiLineUnwrapped => "multi_line_unwrapped",
+                TextboxKind::MultiLineWrapped => "multi_line_wrapped",
+            })
+            .role(Role::TextField)
+            .text_value(text_lens)
+            .cursor(CursorIcon::Text)
+            .default_action_verb(DefaultActionVerb::Focus)
+            .navigable(true)
+    }
+}
+
+/// Builds the menu shown when a textbox is right-clicked, at the position it was opened with
+/// `TextEvent::OpenContextMenu`. The labels can be translated with the `textbox-cut`,
+/// `textbox-copy`, `textbox-paste` and `textbox-select-all` messages.
+fn build_context_menu(cx: &mut Context) {
+    ContextMenu
+        .build(cx, |cx| {
+            context_menu_item(cx, "textbox-cut", "Cut", || TextEvent::Cut)
+                .disabled(TextboxData::has_selection.map(|has_selection| !*has_selection));
+            context_menu_item(cx, "textbox-copy", "Copy", || TextEvent::Copy)
+                .disabled(TextboxData::has_selection.map(|has_selection| !*has_selection));
+            context_menu_item(cx, "textbox-paste", "Paste", || TextEvent::Paste)
+                .disabled(TextboxData::can_paste.map(|can_paste| !*can_paste));
+            context_menu_item(cx, "textbox-select-all", "Select All", || TextEvent::SelectAll);
+        })
+        .class("textbox_context_menu")
+        .role(Role::Menu)
+        .navigable(false)
+        .position_type(PositionType::SelfDirected)
+        .z_order(100)
+        .bind(TextboxData::context_menu_position, |handle, position| {
+            let (x, y) = position.get(handle.cx);
+            handle.left(Pixels(x)).top(Pixels(y));
+        });
+}
+
+fn context_menu_item<'a>(
+    cx: &'a mut Context,
+    key: &str,
+    fallback: &str,
+    action: fn() -> TextEvent,
+) -> Handle<'a, ContextMenuItem> {
+    ContextMenuItem { action }
+        .build(cx, |_| {})
+        .text(Localized::new(key).fallback(fallback))
+        .class("textbox_context_menu_item")
+        .role(Role::MenuItem)
+        .navigable(false)
+}
+
+impl<'a, L: Lens> Handle<'a, Textbox<L>> {
+    /// Replaces the text with `text`, and again whenever it changes if it is a lens, for example
+    /// to seed the textbox with a computed default. Changes are ignored while the text is being
+    /// edited. This is named `set_text` so that it doesn't shadow the `text` style modifier.
+    pub fn set_text<U: ToString>(self, text: impl Res<U>) -> Self {
+        text.set_or_bind(self.cx, self.entity, |cx, entity, text| {
+            cx.emit_to(entity, TextEvent::ResetText(text.to_string()));
+        });
+
+        self
+    }
+
+    /// Returns the entity which clips and scrolls the text, for example to add an overlay in line
+    /// with the visible part of the text.
+    pub fn container_entity(&self) -> Entity {
+        self.find_element("textboxcontainer")
+    }
+
+    /// Returns the entity which lays out and draws the text, for example to attach a tooltip or a
+    /// custom draw to the text itself. Its bounds cover the whole text, including any scrolled out
+    /// of view.
+    pub fn content_entity(&self) -> Entity {
+        self.find_element("textboxlabel")
+    }
+
+    /// Returns the first entity within the textbox with the given element name.
+    fn find_element(&self, element: &str) -> Entity {
+        TreeIterator::subtree(&self.cx.tree, self.entity)
+            .find(|entity| {
+                self.cx.views.get(entity).and_then(|view| view.element()) == Some(element)
+            })
+            .unwrap_or_else(Entity::null)
+    }
+
+    pub fn on_edit<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, String) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnEdit(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Like `on_edit`, but the callback is given a function which returns the text instead of the
+    /// text itself, so that large texts are not copied for the callback on every keystroke when it
+    /// only sometimes needs them.
+    pub fn on_edit_ref<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, &dyn Fn() -> String) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnEditRef(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Sets a callback which is called with each change made to the text, as the byte range that
+    /// was replaced and the text put in its place, for example to pass the changes on to a shared
+    /// document for collaborative editing. Changes made through the bound lens are not reported.
+    pub fn on_change<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, EditDelta) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnChange(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Like `on_edit`, but the callback is only invoked once the text has stopped changing for the
+    /// given duration. Useful for search-as-you-type boxes where each edit triggers expensive work.
+    pub fn on_edit_debounced<F>(self, duration: Duration, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, String) + Send + Sync,
+    {
+        self.cx.emit_to(
+            self.entity,
+            TextEvent::SetOnEditDebounced(Some((duration, Arc::new(callback)))),
+        );
+
+        self
+    }
+
+    /// Sets a highlighter which is run over the text to color ranges of it, for example the tokens
+    /// of a code editor. It runs when set, when the text is replaced, and shortly after the user
+    /// stops editing.
+    pub fn on_highlight<F>(self, highlighter: F) -> Self
+    where
+        F: 'static + Fn(&str) -> Vec<(Range<usize>, Color)> + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnHighlight(Some(Arc::new(highlighter))));
+
+        self
+    }
+
+    /// Sets a spellchecker which returns the byte ranges of the misspelled words in the text, which
+    /// are drawn with a wavy underline. Like the highlighter, it runs when set, when the text is
+    /// replaced, and shortly after the user stops editing.
+    pub fn on_spellcheck<F>(self, spellchecker: F) -> Self
+    where
+        F: 'static + Fn(&str) -> Vec<Range<usize>> + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnSpellcheck(Some(Arc::new(spellchecker))));
+
+        self
+    }
+
+    /// Sets the character which replaces line breaks in text pasted into a single-line textbox,
+    /// or `None` to remove them. Defaults to a space.
+    pub fn newline_replacement(self, replacement: Option<char>) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetNewlineReplacement(replacement));
+
+        self
+    }
+
+    /// Sets whether cutting without a selection cuts the whole line containing the caret, as in
+    /// many code editors. Enabled by default, disable it to make Cut do nothing without a
+    /// selection.
+    pub fn cut_line_when_empty(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetCutLineWhenEmpty(flag));
+
+        self
+    }
+
+    /// Sets whether typing an opening bracket or quote inserts the closing one after the cursor, or
+    /// wraps the selection in the pair. Disabled by default.
+    pub fn auto_pairs(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetAutoPairs(flag));
+
+        self
+    }
+
+    /// Sets the horizontal alignment of the text. Defaults to [`TextAlign::Left`].
+    pub fn text_align(self, align: TextAlign) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetTextAlign(align));
+
+        self.toggle_class("align_center", align == TextAlign::Center)
+            .toggle_class("align_right", align == TextAlign::Right)
+    }
+
+    /// Sets the line ending of the text passed to the `on_edit` and `on_submit` callbacks and put
+    /// on the clipboard. By default the line ending of the bound text is kept, or `\n` is used if
+    /// it has no line breaks.
+    pub fn line_ending(self, line_ending: LineEnding) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetLineEnding(line_ending));
+
+        self
+    }
+
+    /// Sets whether whitespace is removed from the end of each line, and from the start and end of
+    /// the text, when it is submitted. The trimmed text is shown in the textbox and passed to both
+    /// the `on_edit` and `on_submit` callbacks. Disabled by default.
+    pub fn trim_on_submit(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetTrimOnSubmit(flag));
+
+        self
+    }
+
+    /// Sets whether the `on_submit` callback is skipped when the text is the same as when it was
+    /// last loaded or submitted, for example when the textbox loses focus without being edited.
+    /// Disabled by default.
+    pub fn submit_only_if_changed(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetSubmitOnlyIfChanged(flag));
+
+        self
+    }
+
+    /// Sets whether the textbox scrolls to keep the caret visible when its text is replaced, for
+    /// example when the bound data changes, rather than keeping its scroll position. The caret
+    /// stays at the same byte offset where possible. Disabled by default.
+    pub fn scroll_to_caret_on_reset(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetScrollToCaretOnReset(flag));
+
+        self
+    }
+
+    /// Sets whether Tab and Shift+Tab indent and outdent the selected lines, as in a code editor.
+    /// While enabled Tab doesn't move focus out of the textbox as it is being edited, so Escape is
+    /// pressed to stop editing before tabbing away. Disabled by default.
+    pub fn tab_indents(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetTabIndents(flag));
+
+        self
+    }
+
+    /// Sets whether the text can be selected. When disabled the textbox can't be edited and shows
+    /// no caret or selection, unlike a read-only textbox, but its text is still exposed to
+    /// assistive technology. Enabled by default.
+    pub fn selectable(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetSelectable(flag));
+
+        self
+    }
+
+    /// Sets the distance in pixels scrolled by one line of mouse wheel movement. Defaults to the
+    /// same value used by [`ScrollView`].
+    pub fn scroll_sensitivity(self, sensitivity: f32) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetScrollSensitivity(sensitivity));
+
+        self
+    }
+
+    /// Sets whether Home moves to the first non-whitespace character of the line, and to the start
+    /// of the line when pressed again. Disabled by default.
+    pub fn smart_home(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetSmartHome(flag));
+
+        self
+    }
+
+    /// Replaces the key bindings of the textbox, for example to use Emacs-style editing keys.
+    ///
+    /// ```
+    /// # use vizia_core::prelude::*;
+    /// # use vizia_core::text::Movement;
+    /// #
+    /// let key_bindings = KeyBindings::new(false)
+    ///     .bind(KeyChord::new(Modifiers::CTRL, Code::KeyA), TextEvent::MoveCursor(Movement::LineStart, false))
+    ///     .bind(KeyChord::new(Modifiers::CTRL, Code::KeyE), TextEvent::MoveCursor(Movement::LineEnd, false));
+    /// ```
+    pub fn key_bindings(self, key_bindings: KeyBindings) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetKeyBindings(key_bindings));
+
+        self
+    }
+
+    /// Sets whether text can be dragged and dropped into the textbox. Enabled by default.
+    ///
+    /// Text is dropped by sending a [`WindowEvent::Drop`] with [`DropData::Text`]. Of drags from
+    /// other applications, only files are delivered by the winit backend, and their paths are
+    /// inserted as text; the baseview backend doesn't deliver drags from other applications.
+    pub fn accept_drop(self, flag: bool) -> Self {
+        self.modify(|textbox: &mut Textbox<L>| textbox.accept_drop = flag)
+    }
+
+    /// Sets whether a menu with Cut, Copy, Paste and Select All is shown when the textbox is
+    /// right-clicked. Enabled by default, disable it for apps which provide their own menu.
+    pub fn context_menu(self, flag: bool) -> Self {
+        self.modify(|textbox: &mut Textbox<L>| textbox.context_menu = flag)
+    }
+
+    /// Sets whether the textbox acts as a live region, so that screen readers announce changes
+    /// made to its text by the application. Useful for textboxes which display status messages.
+    pub fn live_region(self, flag: bool) -> Self {
+        self.live(if flag { Live::Polite } else { Live::Off })
+    }
+
+    /// Sets the base direction of the text, which decides the visual edge of a line that Home and
+    /// End move to. By default, or when set to `None`, the direction of each line is used, so Home
+    /// moves to the start of the line in reading order.
+    pub fn base_direction(self, direction: Option<BaseDirection>) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetBaseDirection(direction));
+
+        self
+    }
+
+    /// Sets a callback which is called with the text when it is submitted. The flag is `true` if
+    /// Enter was pressed.
+    #[deprecated(note = "use `on_submit_ex`, which reports how the text was submitted")]
+    pub fn on_submit<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, String, bool) + Send + Sync,
+    {
+        self.on_submit_ex(move |cx, text, reason| callback(cx, text, reason == SubmitReason::Enter))
+    }
+
+    /// Sets a callback which is called with the text when it is submitted, along with how it was
+    /// submitted.
+    pub fn on_submit_ex<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, String, SubmitReason) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnSubmit(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Sets a callback which is called when editing is cancelled by pressing Escape, for example
+    /// to revert the text or close an inline editor. It is not called when the text is submitted
+    /// or the textbox loses focus.
+    pub fn on_cancel<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnCancel(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Sets a callback which is called with the selected byte range whenever it changes, or with
+    /// `None` when the selection is removed, whether by the mouse, the keyboard or an event.
+    pub fn on_selection_change<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, Option<(usize, usize)>) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnSelectionChange(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Sets a callback which is called when copying, cutting or pasting fails because the system
+    /// clipboard is unavailable, for example because another application is using it. Cut text is
+    /// kept in the textbox when it can't be put on the clipboard. Without a callback the error is
+    /// printed to standard error.
+    pub fn on_clipboard_error<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, &dyn Error) + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnClipboardError(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Sets a callback which is called with the text being copied or cut, and decides what is put
+    /// on the clipboard. By default the plain text is copied. The clipboard used by vizia only
+    /// carries plain text, so to copy formatted text the callback can write the clipboard itself
+    /// and return [`ClipboardData::Handled`].
+    pub fn on_copy<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, &str) -> ClipboardData + Send + Sync,
+    {
+        self.cx.emit_to(self.entity, TextEvent::SetOnCopy(Some(Arc::new(callback))));
+
+        self
+    }
+
+    /// Sets whether the textbox keeps its text and calls `on_submit_ex` with
+    /// `SubmitReason::FocusLost` when it loses focus, by clicking away or tabbing to another view.
+    /// By default clicking away resets the text to the bound value.
+    pub fn submit_on_blur(self, flag: bool) -> Self {
+        self.modify(|textbox: &mut Textbox<L>| textbox.submit_on_blur = flag)
+    }
+
+    /// Selects the entire contents of the textbox when it receives focus from the keyboard, so that
+    /// typing replaces the old value. Clicking into the textbox still places the caret as usual.
+    pub fn select_all_on_focus(self, flag: bool) -> Self {
+        self.modify(|textbox: &mut Textbox<L>| textbox.select_all_on_focus = flag)
+    }
+
+    /// Sets how quickly presses of the mouse button must follow each other to count as the double
+    /// and triple clicks which select a word or a paragraph. Clicks are told apart by the window,
+    /// so this applies to every view in it, see [`Context::set_multi_click_threshold`].
+    pub fn multi_click_threshold(self, interval: Duration) -> Self {
+        let distance = self.cx.multi_click_distance;
+        self.cx.set_multi_click_threshold(interval, distance);
+
+        self
+    }
+
+    /// Adds a small clear button at the trailing edge of the textbox which empties its contents
+    /// when clicked. The button is only shown while the textbox contains text.
+    pub fn clearable(self, flag: bool) -> Self {
+        if !flag {
+            return self;
+        }
+
+        let entity = self.entity;
+        let mut clear_button = Entity::null();
+        self.cx.with_current(entity, |cx| {
+            clear_button = TextboxClearButton {}
+                .build(cx, |_| {})
+                .class("textbox_clear_button")
+                .text(icons_names::CANCEL)
+                .navigable(false)
+                .focusable(false)
+                .position_type(PositionType::SelfDirected)
+                .display(TextboxData::empty.map(|empty| !*empty))
+                .bind(TextboxData::rtl, |handle, rtl| {
+                    // The button sits at the end of the text, which is the left edge for RTL text.
+                    if rtl.get(handle.cx) {
+                        handle.left(Pixels(2.0)).right(Stretch(1.0));
+                    } else {
+                        handle.left(Stretch(1.0)).right(Pixels(2.0));
+                    }
+                })
+                .entity;
+        });
+
+        self.class("clearable")
+            .modify(|textbox: &mut Textbox<L>| textbox.clear_button = Some(clear_button))
+    }
+
+    /// Adds a vertical scrollbar to a multiline textbox, which is shown while the text is taller
+    /// than the textbox. Dragging the thumb or clicking the track scrolls the text.
+    pub fn show_scrollbar(self, flag: bool) -> Self {
+        if !flag {
+            return self;
+        }
+
+        let entity = self.entity;
+        let mut scrollbar = Entity::null();
+        self.cx.with_current(entity, |cx| {
+            scrollbar = Scrollbar::new(
+                cx,
+                TextboxData::scroll_y,
+                TextboxData::scroll_ratio,
+                Orientation::Vertical,
+                |cx, value| cx.emit(TextEvent::SetScrollY(value)),
+            )
+            .class("textbox_scrollbar")
+            .navigable(false)
+            .position_type(PositionType::SelfDirected)
+            .display(TextboxData::scroll_ratio.map(|ratio| *ratio < 1.0))
+            .entity;
+        });
+
+        self.class("scrollable")
+            .modify(|textbox: &mut Textbox<L>| textbox.scrollbar = Some(scrollbar))
+    }
+
+    /// Sets whether the height of a multiline textbox follows the height of its text, growing as
+    /// lines are added. Use [`max_rows`](Self::max_rows) to limit how tall it grows. Has no effect
+    /// while the textbox is given a height other than `Auto`.
+    pub fn auto_height(self, flag: bool) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetAutoHeight(flag));
+
+        self
+    }
+
+    /// Sets the maximum number of lines a textbox with [`auto_height`](Self::auto_height) grows
+    /// to, after which the text scrolls instead.
+    pub fn max_rows(self, max_rows: Option<usize>) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetMaxRows(max_rows));
+
+        self
+    }
+
+    /// Makes a wrapped multiline textbox grow from `min_rows` up to `max_rows` lines tall as text is
+    /// entered, after which the text scrolls, as in a chat message composer.
+    pub fn auto_grow(self, min_rows: usize, max_rows: usize) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetAutoGrow(min_rows, max_rows));
+
+        self
+    }
+
+    /// Draws a vertical ruler at the given column, measured using the average advance of the font
+    /// of the text, as a guide for wrapping lines by hand. The text itself is not wrapped.
+    pub fn wrap_column(self, column: Option<usize>) -> Self {
+        let entity = self.entity;
+        self.cx.emit_to(entity, TextEvent::SetWrapColumn(column));
+        if column.is_none() {
+            return self;
+        }
+
+        if self.find_element("textboxruler") != Entity::null() {
+            return self;
+        }
+
+        self.cx.with_current(entity, |cx| {
+            TextboxRuler {}
+                .build(cx, |_| {})
+                .class("textbox_ruler")
+                .hoverable(false)
+                .position_type(PositionType::SelfDirected)
+                .left(TextboxData::ruler_x.map(|x| Pixels(x.unwrap_or_default())))
+                .display(TextboxData::ruler_x.map(|x| x.is_some()));
+        });
+
+        self
+    }
+
+    /// Draws tab characters up to the next multiple of `tab_stop` ems from the start of the line,
+    /// for example to line up the columns of tab separated values, or `None` to draw them at the
+    /// width the font gives them, which is the default. Tabs in right-to-left text are left alone.
+    pub fn tab_stop(self, tab_stop: Option<f32>) -> Self {
+        self.cx.emit_to(self.entity, TextEvent::SetTabStop(tab_stop));
+
+        self
+    }
+
+    /// Shows the number of each line in a gutter to the left of the text. Lines which wrap are
+    /// numbered once, at their first row.
+    pub fn line_numbers(self, flag: bool) -> Self {
+        if !flag {
+            return self;
+        }
+
+        let entity = self.entity;
+        self.cx.with_current(entity, |cx| {
+            // All of the numbers are drawn as the lines of a single text, however many there are.
+            let gutter = TextboxGutter {}
+                .build(cx, |cx| {
+                    TextboxLabel {}
+                        .build(cx, |_| {})
+                        .class("textbox_gutter_content")
+                        .hoverable(false)
+                        .text(TextboxData::line_number_text)
+                        .translate(TextboxData::transform.map(|(_, ty)| (0.0, *ty)));
+                })
+                .class("textbox_gutter")
+                .hoverable(false)
+                .navigable(false)
+                .entity;
+
+            // Place the gutter before the text container.
+            cx.tree.set_first_child(gutter).unwrap();
+        });
+        self.cx.emit_to(entity, TextEvent::SetLineNumbers(true));
+
+        self.class("line_numbers")
+    }
+}
+
+impl<L: Lens> View for Textbox<L>
+where
+    <L as Lens>::Target: Data + ToString,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("textbox")
+    }
+
+    fn accessibility(&self, cx: &mut AccessContext, node: &mut AccessNode) {
+        let text_content_id = self.content_entity;
+        if text_content_id == Entity::null() {
+            return;
+        }
+
+        let bounds = cx.cache.get_bounds(text_content_id);
+        let spacing = cx.text_context.spacing(text_content_id);
+
+        // We need a child node per line
+        // let mut children: Vec<(NodeId, NodeBuilder)> = Vec::new();
+        let node_id = node.node_id();
+        cx.text_context.with_editor(text_content_id, |editor| {
+            let cursor = editor.cursor();
+            let selection = editor.select_opt().unwrap_or(cursor);
+
+            let mut selection_active_line = node_id;
+            let mut selection_anchor_line = node_id;
+            let mut selection_active_cursor = 0;
+            let mut selection_anchor_cursor = 0;
+
+            let mut current_cursor = 0;
+            let mut prev_line_index = std::usize::MAX;
+
+            for (index, line) in editor.buffer().layout_runs().enumerate() {
+                let text = line.text;
+                // Positions and widths are those of the drawn text, with widened whitespace.
+                let spaces = widened_spaces(editor.buffer(), &line, spacing);
+                let line_w = widened_width(&line, &spaces);
+
+                let mut line_node = AccessNode::new_from_parent(node_id, index);
+                line_node.set_role(Role::InlineTextBox);
+
+                let line_height = editor.buffer().metrics().line_height as f64;
+                line_node.set_bounds(Rect {
+                    x0: bounds.x as f64,
+                    y0: bounds.y as f64 + line.line_y as f64
+                        - editor.buffer().metrics().font_size as f64,
+                    x1: bounds.x as f64 + line_w as f64,
+                    y1: bounds.y as f64 + line.line_y as f64
+                        - editor.buffer().metrics().font_size as f64
+                        + line_height,
+                });
+                line_node.set_text_direction(if line.rtl {
+                    TextDirection::RightToLeft
+                } else {
+                    TextDirection::LeftToRight
+                });
+
+                let mut character_lengths = Vec::with_capacity(line.glyphs.len());
+                let mut character_positions = Vec::with_capacity(line.glyphs.len());
+                let mut character_widths = Vec::with_capacity(line.glyphs.len());
+
+                // Get the actual text in the line
+                let first_glyph_pos =
+                    line.glyphs.first().map(|glyph| glyph.start).unwrap_or_default();
+                let last_glyph_pos = line.glyphs.last().map(|glyph| glyph.end).unwrap_or_default();
+
+                let mut line_text = text[first_glyph_pos..last_glyph_pos].to_owned();
+
+                let word_lengths =
+                    line_text.unicode_words().map(|word| word.len() as u8).collect::<Vec<_>>();
+
+                let mut line_length = 0;
+
+                for glyph in line.glyphs.iter() {
+                    let length = (glyph.end - glyph.start) as u8;
+
+                    line_length += length as usize;
+
+                    let (position, width) = widen_span(&spaces, glyph.x, glyph.w);
+
+                    character_lengths.push(length);
+                    character_positions.push(position);
+                    character_widths.push(width);
+                }
+
+                // Cosmic strips the newlines but accesskit needs them so we append them back in if line originally ended with a newline
+                // If the last glyph position is equal to the end of the buffer line then this layout run is the last one and ends in a newline.
+                if last_glyph_pos == line.text.len() {
+                    line_text += "\n";
+                    character_lengths.push(1);
+                    character_positions.push(line_w);
+                    character_widths.push(0.0);
+                }
+
+                // TODO: Might need to append any spaces that were stripped during layout. This can be done by
+                // figuring out if the start of the next line is greater than the end of the current line as long
+                // as the lines have the same `line_i`. This will require a peekable iterator loop.
+
+                line_node.set_value(line_text.into_boxed_str());
+                line_node.set_character_lengths(character_lengths.into_boxed_slice());
+                line_node.set_character_positions(character_positions.into_boxed_slice());
+                line_node.set_character_widths(character_widths.into_boxed_slice());
+                line_node.set_word_lengths(word_lengths.into_boxed_slice());
+
+                if line.line_i != prev_line_index {
+                    current_cursor = 0;
+                }
+
+                if line.line_i == cursor.line {
+                    if prev_line_index != line.line_i {
+                        if cursor.index <= line_length {
+                            selection_active_line = line_node.node_id();
+                            selection_active_cursor = cursor.index;
+                        }
+                    } else {
+                        if cursor.index > current_cursor {
+                            selection_active_line = line_node.node_id();
+                            selection_active_cursor = cursor.index - current_cursor;
+                        }
+                    }
+                }
+
+                // Check if the current line contains the cursor or selection
+                // This is a mess because a line happens due to soft and hard breaks but
+                // the cursor and selected indices are relative to the lines caused by hard breaks only.
+                if line.line_i == selection.line {
+                    // A previous line index different to the current means that the current line follows a hard break
+                    if prev_line_index != line.line_i {
+                        if selection.index <= line_length {
+                            selection_anchor_line = line_node.node_id();
+                            selection_anchor_cursor = selection.index;
+                        }
+                    } else {
+                        if selection.index > current_cursor {
+                            selection_anchor_line = line_node.node_id();
+                            selection_anchor_cursor = selection.index - current_cursor;
+                        }
+                    }
+                }
+
+                node.add_child(line_node);
+
+                current_cursor += line_length;
+                prev_line_index = line.line_i;
+            }
+
+            node.set_text_selection(TextSelection {
+                anchor: TextPosition {
+                    node: selection_anchor_line,
+                    character_index: selection_anchor_cursor,
+                },
+                focus: TextPosition {
+                    node: selection_active_line,
+                    character_index: selection_active_cursor,
+                },
+            });
+
+            match self.kind {
+                TextboxKind::MultiLineUnwrapped | TextboxKind::MultiLineWrapped => {
+                    node.node_builder.set_multiline();
+                }
+
+                _ => {
+                    node.node_builder.clear_multiline();
+                }
+            }
+
+            node.node_builder.set_default_action_verb(DefaultActionVerb::Focus);
+        });
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|text_event, _| match text_event {
+            TextEvent::InitContent(content, _) => {
+                self.content_entity = *content;
+            }
+
+            // After a submit, restore the bound value only if the submit callback did not accept
+            // the text.
+            TextEvent::SyncText => {
+                if self.content_entity == Entity::null() {
+                    return;
+                }
+
+                if let Some(source) = cx.data::<L::Source>() {
+                    let bound = self.lens.view(source, |t| {
+                        if let Some(t) = t {
+                            t.to_string()
+                        } else {
+                            "".to_owned()
+                        }
+                    });
+
+                    let text = cx.text_context.with_buffer(self.content_entity, |buf| {
+                        buf.lines.iter().map(|line| line.text()).collect::<Vec<_>>().join("\n")
+                    });
+
+                    if text != bound {
+                        cx.emit(TextEvent::ResetText(bound));
+                    }
+                }
+            }
+
+            _ => {}
+        });
+
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                // Clicks on the scrollbar are handled by the scrollbar. While the textbox is
+                // editing it captures the mouse, so they are forwarded to it.
+                if self.in_scrollbar(cx, cx.hovered()) {
+                    if meta.target == cx.current() {
+                        cx.event_queue.push_back(
+                            Event::new(WindowEvent::MouseDown(MouseButton::Left))
+                                .target(cx.hovered()),
+                        );
+                    }
+                    return;
+                }
+
+                // While the textbox is editing it captures the mouse, so clicks on the context menu
+                // arrive here rather than at the menu.
+                if self.context_menu {
+                    let hovered = cx.hovered();
+                    let view = cx.views.get(&hovered);
+                    if let Some(item) = view.and_then(|view| view.downcast_ref::<ContextMenuItem>())
+                    {
+                        let action = item.action;
+                        if !cx.style.disabled.get(hovered).cloned().unwrap_or_default() {
+                            cx.emit(action());
+                        }
+                        cx.emit(TextEvent::CloseContextMenu);
+                        return;
+                    }
+
+                    if view.map_or(false, |view| view.downcast_ref::<ContextMenu>().is_some()) {
+                        return;
+                    }
+
+                    cx.emit(TextEvent::CloseContextMenu);
+                }
+
+                // A disabled textbox neither takes focus nor captures the mouse.
+                if cx.is_over() && cx.is_disabled() {
+                    return;
+                }
+
+                if self.clear_button == Some(cx.hovered()) {
+                    cx.emit(TextEvent::Clear);
+                } else if cx.is_over() {
                     cx.focus_with_visibility(false);
                     cx.capture();
                     cx.set_checked(true);
+                    cx.lock_cursor_icon();
+
+                    cx.emit(TextEvent::DeadKey(None));
+                    cx.emit(TextEvent::Hit(cx.mouse.cursorx, cx.mouse.cursory));
+                } else if self
+                    .popup
+                    .map_or(false, |popup| cx.hovered().is_descendant_of(cx.tree, popup))
+                {
+                    // The popup ends the edit itself if it needs to, for example when an option is
+                    // picked, so the typed text isn't submitted first.
+                    cx.release();
+                    cx.event_queue.push_back(
+                        Event::new(WindowEvent::MouseDown(MouseButton::Left)).target(cx.hovered()),
+                    );
+                    cx.event_queue.push_back(
+                        Event::new(WindowEvent::PressDown { mouse: true }).target(cx.hovered()),
+                    );
+                } else {
+                    cx.emit(TextEvent::Submit(SubmitReason::FocusLost));
+                    if !self.submit_on_blur {
+                        if let Some(source) = cx.data::<L::Source>() {
+                            let text = self.lens.view(source, |t| {
+                                if let Some(t) = t {
+                                    t.to_string()
+                                } else {
+                                    "".to_owned()
+                                }
+                            });
+
+                            cx.emit(TextEvent::ResetText(text));
+                        };
+                    }
+                    cx.release();
+                    cx.set_checked(false);
+
+                    // Forward event to hovered
+                    cx.event_queue.push_back(
+                        Event::new(WindowEvent::MouseDown(MouseButton::Left)).target(cx.hovered()),
+                    );
+                    cx.event_queue.push_back(
+                        Event::new(WindowEvent::PressDown { mouse: true }).target(cx.hovered()),
+                    );
+                }
+            }
+
+            #[cfg(feature = "clipboard")]
+            WindowEvent::MouseDown(MouseButton::Middle) => {
+                // Paste the primary selection at the click point, as is conventional on X11 and
+                // Wayland.
+                if cx.is_over() && !cx.is_disabled() {
+                    if let Ok(text) = cx.get_primary_selection() {
+                        if !text.is_empty() {
+                            cx.focus_with_visibility(false);
+                            cx.emit(TextEvent::StartEdit);
+                            cx.emit(TextEvent::Hit(cx.mouse.cursorx, cx.mouse.cursory));
+                            cx.emit(TextEvent::InsertText(text));
+                        }
+                    }
+                }
+            }
+
+            WindowEvent::DragOver(_) => {
+                if self.accept_drop && !cx.is_disabled() {
+                    cx.emit(TextEvent::DragOver(cx.mouse.cursorx, cx.mouse.cursory));
+                }
+            }
+
+            WindowEvent::DragLeave => {
+                cx.emit(TextEvent::DragLeave);
+            }
+
+            WindowEvent::Drop(data) => {
+                if self.accept_drop && !cx.is_disabled() {
+                    let text = match data {
+                        DropData::Text(text) => text.clone(),
+                        DropData::File(path) => path.display().to_string(),
+                    };
+                    cx.focus_with_visibility(false);
+                    cx.emit(TextEvent::StartEdit);
+                    cx.emit(TextEvent::DropText(cx.mouse.cursorx, cx.mouse.cursory, text));
+                }
+            }
+
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                if self.context_menu && cx.is_over() {
+                    // The menu is positioned relative to the textbox, in logical pixels.
+                    let scale = cx.style.dpi_factor as f32;
+                    let bounds = cx.cache.get_bounds(cx.current());
+                    let x = (cx.mouse.cursorx - bounds.x) / scale;
+                    let y = (cx.mouse.cursory - bounds.y) / scale;
+                    cx.emit(TextEvent::StartEdit);
+                    cx.emit(TextEvent::OpenContextMenu(x, y));
+                } else {
+                    cx.emit(TextEvent::CloseContextMenu);
+                }
+            }
+
+            WindowEvent::FocusIn => {
+                if cx.mouse.left.pressed != cx.current()
+                    || cx.mouse.left.state == MouseButtonState::Released
+                {
+                    cx.emit(TextEvent::StartEdit);
+                    if self.select_all_on_focus {
+                        cx.emit(TextEvent::SelectAll);
+                    }
+                }
+            }
+
+            WindowEvent::FocusOut => {
+                // Clicking away has already submitted and unchecked the textbox.
+                if self.submit_on_blur && cx.is_checked() {
+                    cx.emit(TextEvent::Submit(SubmitReason::FocusLost));
+                } else {
+                    cx.emit(TextEvent::EndEdit);
+                }
+            }
+
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                cx.emit(TextEvent::SelectWord);
+            }
+
+            WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                cx.emit(TextEvent::SelectParagraph);
+            }
+
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                cx.unlock_cursor_icon();
+                // The scrollbar releases the mouse when its thumb is let go, so take it back to
+                // keep editing.
+                if self.in_scrollbar(cx, cx.mouse.left.pressed) {
+                    if cx.is_checked() {
+                        cx.capture();
+                    }
+                    return;
+                }
+
+                if cx.mouse.left.pressed == cx.current() {
+                    cx.emit(TextEvent::StartEdit);
+                    // Holding Ctrl copies rather than moves dragged text.
+                    cx.emit(TextEvent::EndDrag(
+                        cx.mouse.cursorx,
+                        cx.mouse.cursory,
+                        cx.modifiers.contains(Modifiers::CTRL),
+                    ));
+                }
+            }
+
+            WindowEvent::MouseMove(_, _) => {
+                if cx.mouse.left.state == MouseButtonState::Pressed
+                    && cx.mouse.left.pressed == cx.current
+                {
+                    cx.emit(TextEvent::Drag(cx.mouse.cursorx, cx.mouse.cursory));
+                }
+            }
+
+            WindowEvent::MouseScroll(x, y) => {
+                // Holding Shift turns the vertical wheel into horizontal scrolling.
+                let (x, y) =
+                    if cx.modifiers.contains(Modifiers::SHIFT) { (*y, *x) } else { (*x, *y) };
+                cx.emit(TextEvent::Scroll(x, y));
+            }
+
+            WindowEvent::ScaleFactorChanged => {
+                // The text must be reshaped at the new scale before the caret and scroll
+                // transform can be recomputed.
+                cx.emit(TextEvent::ScaleFactorChanged);
+            }
+
+            WindowEvent::CharInput(c) => {
+                if *c != '\u{1b}' && // Escape
+                            *c != '\u{8}' && // Backspace
+                            *c != '\u{9}' && // Tab
+                            *c != '\u{7f}' && // Delete
+                            *c != '\u{0d}' && // Carriage return
+                            *c != '\u{0a}' && // Line feed, line breaks come from the Enter binding
+                            !cx.modifiers.contains(command_modifier())
+                {
+                    cx.emit(TextEvent::InsertText(String::from(*c)));
+                }
+            }
+
+            WindowEvent::ImePreedit(text, cursor) => {
+                cx.emit(TextEvent::SetPreedit(text.clone(), *cursor));
+            }
+
+            WindowEvent::ImeCommit(text) => {
+                cx.emit(TextEvent::InsertText(text.clone()));
+            }
+
+            // The winit backend composes accented characters itself, while the baseview backend
+            // reports dead keys, in which case the textbox composes them with the next character
+            // typed. Only combinations with a precomposed character are composed this way.
+            WindowEvent::KeyDown(_, Some(Key::Dead(Some(dead_key)))) => {
+                cx.emit(TextEvent::DeadKey(Some(*dead_key)));
+            }
+
+            WindowEvent::KeyDown(code, _) => {
+                let chord = KeyChord::new(*cx.modifiers, *code);
+                if let Some(event) = cx
+                    .data::<TextboxData>()
+                    .and_then(|text_data| text_data.key_bindings.get(&chord))
+                    .cloned()
+                {
+                    // A key which moves the caret or edits the text doesn't type a character, so
+                    // a pending dead key isn't composed with whatever is typed after it.
+                    cx.emit(TextEvent::DeadKey(None));
+                    cx.emit(event);
                 }
             }
 
-            TextEvent::EndEdit => {
-                self.deselect(cx);
-                self.edit = false;
-                cx.set_checked(false);
-                cx.release();
-            }
+            WindowEvent::ActionRequest(ActionRequest {
+                action: accesskit::Action::ReplaceSelectedText,
+                target: _,
+                data: Some(ActionData::Value(value)),
+            }) => {
+                // Replaces the selection, or inserts at the cursor, as if the text had been typed.
+                cx.emit(TextEvent::StartEdit);
+                cx.emit(TextEvent::InsertText(value.to_string()));
+            }
+
+            WindowEvent::ActionRequest(ActionRequest {
+                action: accesskit::Action::SetTextSelection,
+                target: _,
+                data: Some(ActionData::SetTextSelection(selection)),
+            }) => {
+                let node_id = cx.current.accesskit_id();
+                cx.text_context.with_editor(self.content_entity, |editor| {
+                    let runs = editor
+                        .buffer()
+                        .layout_runs()
+                        .map(|line| {
+                            let first_glyph_pos =
+                                line.glyphs.first().map(|glyph| glyph.start).unwrap_or_default();
+                            let last_glyph_pos =
+                                line.glyphs.last().map(|glyph| glyph.end).unwrap_or_default();
+                            (line.line_i, last_glyph_pos - first_glyph_pos)
+                        })
+                        .collect::<Vec<_>>();
+
+                    let anchor = access_position_to_cursor(node_id, &runs, &selection.anchor);
+                    let focus = access_position_to_cursor(node_id, &runs, &selection.focus);
+                    if let (Some(anchor), Some(focus)) = (anchor, focus) {
+                        set_editor_cursor(editor, focus);
+                        editor.set_select_opt(if anchor == focus { None } else { Some(anchor) });
+                    }
+                });
+                cx.needs_redraw();
+            }
+
+            _ => {}
+        });
+    }
+}
+
+/// Converts a position reported by assistive technology, made up of a line node and an index into
+/// that line, into a cursor in the buffer. `runs` holds the buffer line index and length of each
+/// layout run, in the order the line nodes are created in `accessibility`.
+fn access_position_to_cursor(
+    node_id: NodeId,
+    runs: &[(usize, usize)],
+    position: &TextPosition,
+) -> Option<Cursor> {
+    let mut run_start = 0;
+    let mut prev_line_index = std::usize::MAX;
+    for (index, &(line_i, length)) in runs.iter().enumerate() {
+        // Runs following a soft break continue from the end of the previous run of the same line.
+        if line_i != prev_line_index {
+            run_start = 0;
+        }
+
+        if AccessNode::new_from_parent(node_id, index).node_id() == position.node {
+            return Some(Cursor::new(line_i, run_start + position.character_index));
+        }
+
+        run_start += length;
+        prev_line_index = line_i;
+    }
+
+    None
+}
+
+// can't just be a stack because what if you've styled stacks
+pub struct TextboxContainer {}
+impl View for TextboxContainer {
+    fn element(&self) -> Option<&'static str> {
+        Some("textboxcontainer")
+    }
+}
+
+// Non-focusable button shown by `Textbox::clearable`, clicks are handled by the textbox itself
+pub struct TextboxClearButton {}
+impl View for TextboxClearButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("textboxclearbutton")
+    }
+}
+
+// Shows the line numbers of a textbox, built by `Textbox::line_numbers`
+pub struct TextboxGutter {}
+impl View for TextboxGutter {
+    fn element(&self) -> Option<&'static str> {
+        Some("textboxgutter")
+    }
+}
+
+// can't just be a label because what if you've styled labels
+pub struct TextboxLabel {}
+impl View for TextboxLabel {
+    fn element(&self) -> Option<&'static str> {
+        Some("textboxlabel")
+    }
+}
+
+// Vertical line drawn at the column set with `Textbox::wrap_column`
+pub struct TextboxRuler {}
+impl View for TextboxRuler {
+    fn element(&self) -> Option<&'static str> {
+        Some("textboxruler")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::backend::BackendContext;
+    use crate::events::{EventManager, ViewHandler};
+    use std::num::NonZeroU64;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn newline_replacement() {
+        assert_eq!(replace_newlines("one\ntwo\r\nthree\rfour", Some(' ')), "one two three four");
+        assert_eq!(replace_newlines("one\r\n\ntwo", None), "onetwo");
+        assert_eq!(replace_newlines("one", Some('⏎')), "one");
+    }
+
+    #[test]
+    fn dead_keys() {
+        assert_eq!(compose_dead_key('´', "e"), "é");
+        assert_eq!(compose_dead_key('^', "A"), "Â");
+        assert_eq!(compose_dead_key('¨', "u"), "ü");
+        assert_eq!(compose_dead_key('¸', "c"), "ç");
+        assert_eq!(compose_dead_key('´', " "), "´");
+        assert_eq!(compose_dead_key('`', "`"), "`");
+        assert_eq!(compose_dead_key('´', "x"), "´x");
+    }
+
+    #[test]
+    fn trim_on_submit() {
+        assert_eq!(trim_whitespace("  hello world \t"), "hello world");
+        assert_eq!(trim_whitespace("one  \n  two\t\n\n"), "one\n  two");
+        assert_eq!(trim_whitespace(" \n "), "");
+    }
+
+    #[test]
+    fn submit_only_if_changed() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+
+        let submits = Arc::new(AtomicUsize::new(0));
+        let counter = submits.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .submit_only_if_changed(true)
+            .on_submit_ex(move |_, _, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .entity();
 
-            TextEvent::Submit(reason) => {
-                if let Some(callback) = self.on_submit.take() {
-                    let text = self.clone_text(cx);
-                    (callback)(cx, text, *reason);
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                    self.on_submit = Some(callback);
-                }
-                cx.emit(TextEvent::EndEdit);
-            }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::Submit(SubmitReason::FocusLost));
+        while event_manager.flush_events(&mut cx) {}
+        assert_eq!(submits.load(Ordering::SeqCst), 0);
 
-            TextEvent::SelectAll => {
-                self.select_all(cx);
-                self.set_caret(cx);
-            }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("!".to_owned()));
+        cx.emit_to(textbox, TextEvent::Submit(SubmitReason::Enter));
+        while event_manager.flush_events(&mut cx) {}
+        assert_eq!(submits.load(Ordering::SeqCst), 1);
+    }
 
-            TextEvent::SelectWord => {
-                self.select_word(cx);
-                self.set_caret(cx);
-            }
+    #[test]
+    fn crlf_text_is_unchanged_until_edited() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "one\r\ntwo".to_owned() }.build(&mut cx);
+
+        let submits = Arc::new(AtomicUsize::new(0));
+        let counter = submits.clone();
+        let textbox = Textbox::new_multiline(&mut cx, TestData::text, false)
+            .submit_only_if_changed(true)
+            .on_submit_ex(move |_, _, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .entity();
 
-            TextEvent::SelectParagraph => {
-                self.select_paragraph(cx);
-                self.set_caret(cx);
-            }
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-            // TextEvent::SetSelection(selection) => {
-            //     self.selection = *selection;
-            // }
-            TextEvent::Hit(posx, posy) => {
-                self.hit(cx, *posx, *posy);
-                self.set_caret(cx);
-            }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::Submit(SubmitReason::Programmatic));
+        while event_manager.flush_events(&mut cx) {}
+        assert_eq!(submits.load(Ordering::SeqCst), 0);
+    }
 
-            TextEvent::Drag(posx, posy) => {
-                self.drag(cx, *posx, *posy);
-                self.set_caret(cx);
-            }
+    #[test]
+    fn context_menu_is_built_while_open() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let textbox = Textbox::new(&mut cx, TestData::text).entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        let items = |cx: &Context| {
+            cx.views
+                .values()
+                .filter(|view| view.downcast_ref::<ContextMenuItem>().is_some())
+                .count()
+        };
+        assert_eq!(items(&cx), 0);
+
+        cx.emit_to(textbox, TextEvent::OpenContextMenu(10.0, 10.0));
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_data_updates();
+        assert_eq!(items(&cx), 4);
+
+        cx.emit_to(textbox, TextEvent::CloseContextMenu);
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_data_updates();
+        assert_eq!(items(&cx), 0);
+    }
 
-            TextEvent::Scroll(x, y) => {
-                self.scroll(cx, *x, *y);
-            }
+    #[test]
+    fn macos_move_line_bindings_keep_paragraph_movement() {
+        let key_bindings = KeyBindings::for_platform(true, true);
+        assert!(matches!(
+            key_bindings.get(&KeyChord::new(Modifiers::ALT, Code::ArrowUp)),
+            Some(TextEvent::MoveCursor(Movement::Paragraph(Direction::Upstream), false))
+        ));
+        assert!(matches!(
+            key_bindings.get(&KeyChord::new(Modifiers::ALT | Modifiers::SHIFT, Code::ArrowDown)),
+            Some(TextEvent::MoveCursor(Movement::Paragraph(Direction::Downstream), true))
+        ));
+        assert!(matches!(
+            key_bindings.get(&KeyChord::new(Modifiers::LOGO | Modifiers::ALT, Code::BracketLeft)),
+            Some(TextEvent::MoveLineUp)
+        ));
+        assert!(matches!(
+            key_bindings.get(&KeyChord::new(Modifiers::LOGO | Modifiers::ALT, Code::ArrowDown)),
+            Some(TextEvent::AddCursorBelow)
+        ));
+
+        let key_bindings = KeyBindings::for_platform(true, false);
+        assert!(matches!(
+            key_bindings.get(&KeyChord::new(Modifiers::ALT, Code::ArrowUp)),
+            Some(TextEvent::MoveLineUp)
+        ));
+    }
 
-            TextEvent::Copy =>
-            {
-                #[cfg(feature = "clipboard")]
-                if self.edit {
-                    if let Some(selected_text) = self.clone_selected(cx) {
-                        if !selected_text.is_empty() {
-                            cx.set_clipboard(selected_text)
-                                .expect("Failed to add text to clipboard");
-                        }
-                    }
-                }
-            }
+    #[test]
+    fn tab_indents_locks_focus_while_editing() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let textbox =
+            Textbox::new_multiline(&mut cx, TestData::text, false).tab_indents(true).entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_data_updates();
+        assert_eq!(cx.tree.lock_focus_within(textbox), Entity::root());
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_data_updates();
+        assert_eq!(cx.tree.lock_focus_within(textbox), textbox);
+
+        cx.emit_to(textbox, TextEvent::Cancel);
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_data_updates();
+        assert_eq!(cx.tree.lock_focus_within(textbox), Entity::root());
+    }
 
-            TextEvent::Paste =>
-            {
-                #[cfg(feature = "clipboard")]
-                if self.edit {
-                    if let Ok(text) = cx.get_clipboard() {
-                        cx.emit(TextEvent::InsertText(text));
-                    }
-                }
-            }
+    #[test]
+    fn ime_events_reach_focused_textbox() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: String::new() }.build(&mut cx);
+
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        let log = edits.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_edit(move |_, text| log.lock().unwrap().push(text));
+        let content = textbox.content_entity();
+        let textbox = textbox.entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        while event_manager.flush_events(&mut cx) {}
+
+        // The backend sends input method events to the root rather than to the focused view.
+        BackendContext::new(&mut cx).emit_origin(WindowEvent::ImePreedit("に".to_owned(), None));
+        while event_manager.flush_events(&mut cx) {}
+        assert_eq!(
+            cx.text_context.with_buffer(content, |buf| buf.lines[0].text().to_owned()),
+            "に"
+        );
+
+        BackendContext::new(&mut cx).emit_origin(WindowEvent::ImeCommit("日本".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
+        assert_eq!(*edits.lock().unwrap(), ["日本"]);
+    }
+
+    #[test]
+    fn drop_elsewhere_clears_drop_caret() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let textbox = Textbox::new(&mut cx, TestData::text).entity();
+        let other = Element::new(&mut cx).entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        // The drag moved off of the textbox before the data was dropped onto another view.
+        cx.text_context.set_drop_caret(textbox, Some(2));
+        cx.hovered = other;
+        cx.emit_custom(
+            Event::new(WindowEvent::Drop(DropData::Text("a".to_owned())))
+                .origin(Entity::root())
+                .target(other),
+        );
+        while event_manager.flush_events(&mut cx) {}
+
+        assert_eq!(cx.text_context.drop_caret(textbox), None);
+    }
+
+    #[test]
+    fn auto_height_leaves_fixed_height() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let growing =
+            Textbox::new_multiline(&mut cx, TestData::text, true).auto_height(true).entity();
+        let fixed = Textbox::new_multiline(&mut cx, TestData::text, true)
+            .auto_height(true)
+            .height(Pixels(50.0))
+            .entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        for textbox in [growing, fixed] {
+            cx.emit_to(textbox, TextEvent::StartEdit);
+            cx.emit_to(textbox, TextEvent::InsertText("\na\nb".to_owned()));
+        }
+        while event_manager.flush_events(&mut cx) {}
+
+        assert!(cx.style.min_height.get_inline(growing).is_some());
+        assert_eq!(cx.style.height.get(fixed), Some(&Pixels(50.0)));
+        assert!(cx.style.min_height.get_inline(fixed).is_none());
+    }
 
-            TextEvent::Cut =>
+    #[test]
+    fn multi_click_threshold() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        Textbox::new(&mut cx, TestData::text).multi_click_threshold(Duration::from_millis(800));
+        cx.set_multi_click_threshold(cx.multi_click_interval, 4.0);
+
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = now.clone();
+        cx.set_clock(move || *clock.lock().unwrap());
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        let mut click = |cx: &mut Context, x: f32, delay: u64| {
+            *now.lock().unwrap() += Duration::from_millis(delay);
+            cx.mouse.cursorx = x;
+            for event in
+                [WindowEvent::MouseDown(MouseButton::Left), WindowEvent::MouseUp(MouseButton::Left)]
             {
-                #[cfg(feature = "clipboard")]
-                if self.edit {
-                    if let Some(selected_text) = self.clone_selected(cx) {
-                        if !selected_text.is_empty() {
-                            cx.set_clipboard(selected_text)
-                                .expect("Failed to add text to clipboard");
-                            self.delete_text(cx, Movement::Grapheme(Direction::Upstream));
-                            if let Some(callback) = self.on_edit.take() {
-                                let text = self.clone_text(cx);
-                                (callback)(cx, text);
-
-                                self.on_edit = Some(callback);
-                            }
-                        }
-                    }
-                }
+                cx.emit_custom(Event::new(event).origin(Entity::root()).target(Entity::root()));
             }
+            while event_manager.flush_events(cx) {}
+            cx.clicks
+        };
+
+        assert_eq!(click(&mut cx, 10.0, 1000), 1);
+        // Slower than the default interval and slightly moved.
+        assert_eq!(click(&mut cx, 13.0, 700), 2);
+        assert_eq!(click(&mut cx, 13.0, 700), 3);
+        // Too far away, then too late.
+        assert_eq!(click(&mut cx, 20.0, 100), 1);
+        assert_eq!(click(&mut cx, 20.0, 900), 1);
+    }
 
-            TextEvent::SetOnEdit(on_edit) => {
-                self.on_edit = on_edit.clone();
-            }
+    #[test]
+    fn vertical_moves_keep_goal_column() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::with_content(cx, content, "abcdefgh\nab\nabcdefgh", true);
+        data.select_range(cx, 6..6);
+
+        // The short line moves the caret to its end, and the next long line moves it back to the
+        // column it set out from, going down and then up again.
+        data.move_cursor(cx, Movement::Line(Direction::Downstream), false);
+        assert_eq!(data.selection_range(cx), 11..11);
+        data.move_cursor(cx, Movement::Line(Direction::Downstream), false);
+        assert_eq!(data.selection_range(cx), 18..18);
+        data.move_cursor(cx, Movement::Line(Direction::Upstream), false);
+        assert_eq!(data.selection_range(cx), 11..11);
+        data.move_cursor(cx, Movement::Line(Direction::Upstream), false);
+        assert_eq!(data.selection_range(cx), 6..6);
+
+        // A horizontal move sets a new column.
+        data.move_cursor(cx, Movement::Grapheme(Direction::Upstream), false);
+        data.move_cursor(cx, Movement::Line(Direction::Downstream), false);
+        data.move_cursor(cx, Movement::Line(Direction::Downstream), false);
+        assert_eq!(data.selection_range(cx), 17..17);
+    }
 
-            TextEvent::InitContent(content, kind) => {
-                self.content_entity = *content;
-                self.kind = *kind;
-            }
+    #[test]
+    fn measure_is_kept_until_text_changes() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
 
-            TextEvent::GeometryChanged => {
-                self.set_caret(cx);
-            }
+        let mut data = TextboxData::with_content(cx, content, "one", true);
+        let (_, height) = data.measure(cx);
 
-            TextEvent::SetOnSubmit(on_submit) => {
-                self.on_submit = on_submit.clone();
-            }
+        // Text set behind the back of the textbox isn't measured.
+        cx.text_context.with_buffer(content, |buf| buf.set_text("a\nb\nc", Attrs::new()));
+        assert_eq!(data.measure(cx).1, height);
+
+        data.reset_text(cx, "one");
+        data.select_range(cx, 3..3);
+        data.insert_text(cx, "\ntwo");
+        assert_eq!(data.measure(cx).1, 2.0 * height);
+    }
+
+    #[test]
+    fn line_numbers_follow_wrapped_rows() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::with_content(cx, content, "one\ntwo words here\nthree", true);
+        data.update_line_numbers(cx);
+        assert_eq!(data.line_number_text, "1\n2\n3");
+
+        // Each word of the second line is wrapped onto a row of its own.
+        cx.text_context.with_buffer(content, |buf| {
+            buf.set_wrap(cosmic_text::Wrap::Word);
+            buf.set_size(1, i32::MAX);
         });
+        data.update_line_numbers(cx);
+        assert_eq!(data.line_number_text, "1\n2\n\n\n3");
     }
-}
 
-pub struct Textbox<L: Lens> {
-    lens: L,
-    kind: TextboxKind,
-}
+    #[test]
+    fn wrap_column_reuses_ruler() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        Textbox::new_multiline(&mut cx, TestData::text, false)
+            .wrap_column(Some(80))
+            .wrap_column(Some(100));
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub enum TextboxKind {
-    SingleLine,
-    MultiLineUnwrapped,
-    MultiLineWrapped,
-}
+        let rulers = cx.views.values().filter(|view| view.element() == Some("textboxruler"));
+        assert_eq!(rulers.count(), 1);
+    }
 
-impl<L: Lens> Textbox<L>
-where
-    <L as Lens>::Target: Data + Clone + ToString,
-{
-    pub fn new(cx: &mut Context, lens: L) -> Handle<Self> {
-        Self::new_core(cx, lens, TextboxKind::SingleLine)
+    #[test]
+    fn multiline_submit_binding() {
+        let chord = KeyChord::new(command_modifier(), Code::Enter);
+        assert!(matches!(
+            KeyBindings::new(true).get(&chord),
+            Some(TextEvent::Submit(SubmitReason::Enter))
+        ));
+        assert!(matches!(
+            KeyBindings::new(true).get(&KeyChord::new(Modifiers::empty(), Code::Enter)),
+            Some(TextEvent::InsertText(_))
+        ));
     }
 
-    pub fn new_multiline(cx: &mut Context, lens: L, wrap: bool) -> Handle<Self> {
-        Self::new_core(
-            cx,
-            lens,
-            if wrap { TextboxKind::MultiLineWrapped } else { TextboxKind::MultiLineUnwrapped },
-        )
+    #[test]
+    fn bound_key_forgets_dead_key() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: String::new() }.build(&mut cx);
+        let textbox = Textbox::new(&mut cx, TestData::text);
+        let content = textbox.content_entity();
+        let textbox = textbox.entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, WindowEvent::KeyDown(Code::BracketLeft, Some(Key::Dead(Some('´')))));
+        cx.emit_to(textbox, WindowEvent::KeyDown(Code::ArrowLeft, Some(Key::ArrowLeft)));
+        cx.emit_to(textbox, WindowEvent::CharInput('e'));
+        while event_manager.flush_events(&mut cx) {}
+
+        assert_eq!(cx.text_context.with_buffer(content, |buf| buf.lines[0].text().to_owned()), "e");
     }
 
-    fn new_core(cx: &mut Context, lens: L, kind: TextboxKind) -> Handle<Self> {
-        let text_lens = lens.clone();
-        // TODO can this be simplified now that text doesn't live in TextboxData?
-        let result = Self { lens: lens.clone(), kind }.build(cx, move |cx| {
-            Binding::new(cx, lens.clone(), |cx, text| {
-                let text_str = text.view(cx.data().unwrap(), |text| {
-                    text.map(|x| x.to_string()).unwrap_or_else(|| "".to_owned())
-                });
-                if let Some(text_data) = cx.data::<TextboxData>() {
-                    if !text_data.edit {
-                        let td = TextboxData {
-                            edit: text_data.edit,
-                            transform: text_data.transform,
-                            on_edit: text_data.on_edit.clone(),
-                            content_entity: text_data.content_entity,
-                            kind: text_data.kind,
-                            on_submit: text_data.on_submit.clone(),
-                        };
-                        cx.text_context.with_buffer(text_data.content_entity, |buf| {
-                            buf.set_text(&text_str, Attrs::new());
-                        });
-                        let parent = cx.current().parent(&cx.tree).unwrap();
-                        cx.with_current(parent, |cx| td.build(cx));
-                        // push an event into the queue to force an update because the textbox data
-                        // may have already been observed this update cycle
-                        cx.emit_to(cx.current(), ());
-                    }
-                } else {
-                    let mut td = TextboxData::new();
-                    td.set_caret(&mut EventContext::new(cx));
-                    let parent = cx.current().parent(&cx.tree).unwrap();
-                    cx.with_current(parent, |cx| td.build(cx));
-                    cx.emit_to(cx.current(), ());
-                }
-            });
-            let text = lens.view(cx.data().unwrap(), |text| {
-                text.map(|x| x.to_string()).unwrap_or_else(|| "".to_owned())
-            });
-            TextboxContainer {}
-                .build(cx, move |cx| {
-                    let lbl = TextboxLabel {}
-                        .build(cx, |_| {})
-                        .hidden(true)
-                        .navigable(false)
-                        .hoverable(false)
-                        .class("textbox_content")
-                        .text(&text)
-                        .translate(TextboxData::transform)
-                        .on_geo_changed(|cx, _| cx.emit(TextEvent::GeometryChanged))
-                        .entity;
+    #[test]
+    fn single_line_shift_enter_does_nothing() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
 
-                    cx.emit(TextEvent::InitContent(lbl, kind));
-                    cx.text_context.with_buffer(lbl, |buf| {
-                        buf.set_text(&text, Attrs::new());
-                    });
-                })
-                .hidden(true)
-                .navigable(false)
-                .hoverable(false)
-                .class("textbox_container");
+        let submits = Arc::new(AtomicUsize::new(0));
+        let counter = submits.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text).on_submit_ex(move |_, _, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
         });
+        let content = textbox.content_entity();
+        let textbox = textbox.entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.modifiers = Modifiers::SHIFT;
+        cx.emit_to(textbox, WindowEvent::KeyDown(Code::Enter, None));
+        cx.emit_to(textbox, WindowEvent::CharInput('\n'));
+        while event_manager.flush_events(&mut cx) {}
+
+        assert_eq!(submits.load(Ordering::SeqCst), 0);
+        assert_eq!(cx.text_context.with_buffer(content, |buf| buf.lines.len()), 1);
+        assert_eq!(
+            cx.text_context.with_buffer(content, |buf| buf.lines[0].text().to_owned()),
+            "hello"
+        );
+    }
 
-        result
-            .class(match kind {
-                TextboxKind::SingleLine => "single_line",
-                TextboxKind::MultiLineUnwrapped => "multi_line_unwrapped",
-                TextboxKind::MultiLineWrapped => "multi_line_wrapped",
-            })
-            .role(Role::TextField)
-            .text_value(text_lens)
-            .cursor(CursorIcon::Text)
-            .default_action_verb(DefaultActionVerb::Focus)
-            .navigable(true)
+    #[test]
+    fn case_transform() {
+        assert_eq!(transform_case("straße", CaseTransform::Upper), "STRASSE");
+        assert_eq!(transform_case("ΣΟΦΙΑ", CaseTransform::Lower), "σοφια");
+        assert_eq!(
+            transform_case("hello wORLD, it's éte", CaseTransform::Title),
+            "Hello World, It's Éte"
+        );
     }
-}
 
-impl<'a, L: Lens> Handle<'a, Textbox<L>> {
-    pub fn on_edit<F>(self, callback: F) -> Self
-    where
-        F: 'static + Fn(&mut EventContext, String) + Send + Sync,
-    {
-        self.cx.emit_to(self.entity, TextEvent::SetOnEdit(Some(Arc::new(callback))));
+    #[test]
+    fn rtl_home_end() {
+        let hebrew = "שלום עולם";
+        let arabic = "مرحبا بالعالم";
+
+        for text in [hebrew, arabic] {
+            let line = 0..text.len();
+            // Following the line direction, Home and End go to the logical start and end.
+            assert_eq!(line_edge(line.clone(), true, None, true), 0);
+            assert_eq!(line_edge(line.clone(), true, None, false), text.len());
+            // A right-to-left base direction agrees with the line.
+            assert_eq!(line_edge(line.clone(), true, Some(true), true), 0);
+            // With a left-to-right base direction Home goes to the visual left, the logical end.
+            assert_eq!(line_edge(line.clone(), true, Some(false), true), text.len());
+            assert_eq!(line_edge(line, true, Some(false), false), 0);
+        }
 
-        self
+        // Left-to-right text in a right-to-left textbox.
+        assert_eq!(line_edge(4..9, false, Some(true), true), 9);
+        assert_eq!(line_edge(4..9, false, None, true), 4);
     }
 
-    pub fn on_submit<F>(self, callback: F) -> Self
-    where
-        F: 'static + Fn(&mut EventContext, String, bool) + Send + Sync,
-    {
-        self.cx.emit_to(self.entity, TextEvent::SetOnSubmit(Some(Arc::new(callback))));
+    #[test]
+    fn rtl_home_end_in_shaped_text() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let hebrew = "שלום עולם";
+        let mut data = TextboxData::with_content(cx, content, hebrew, false);
+        data.select_range(cx, 4..4);
+        data.move_cursor(cx, Movement::LineStart, false);
+        assert_eq!(data.selection_range(cx), 0..0);
+        data.move_cursor(cx, Movement::LineEnd, false);
+        assert_eq!(data.selection_range(cx), hebrew.len()..hebrew.len());
+
+        // Home goes to the visual left of the line, which is its logical end.
+        data.base_direction = Some(BaseDirection::LeftToRight);
+        data.select_range(cx, 4..4);
+        data.move_cursor(cx, Movement::LineStart, false);
+        assert_eq!(data.selection_range(cx), hebrew.len()..hebrew.len());
+        data.move_cursor(cx, Movement::LineEnd, false);
+        assert_eq!(data.selection_range(cx), 0..0);
+    }
 
-        self
+    #[test]
+    fn text_selection_across_lines() {
+        let node_id = NodeId::from(NonZeroU64::new(1).unwrap());
+        let line_node = |index| AccessNode::new_from_parent(node_id, index).node_id();
+
+        // "hello world" wrapped after "hello ", followed by a second paragraph "foo".
+        let runs = [(0, 6), (0, 5), (1, 3)];
+        let selection = TextSelection {
+            anchor: TextPosition { node: line_node(1), character_index: 2 },
+            focus: TextPosition { node: line_node(2), character_index: 1 },
+        };
+
+        assert_eq!(
+            access_position_to_cursor(node_id, &runs, &selection.anchor),
+            Some(Cursor::new(0, 8))
+        );
+        assert_eq!(
+            access_position_to_cursor(node_id, &runs, &selection.focus),
+            Some(Cursor::new(1, 1))
+        );
+        assert_eq!(
+            access_position_to_cursor(
+                node_id,
+                &runs,
+                &TextPosition { node: line_node(3), character_index: 0 }
+            ),
+            None
+        );
     }
-}
 
-impl<L: Lens> View for Textbox<L>
-where
-    <L as Lens>::Target: Data + ToString,
-{
-    fn element(&self) -> Option<&'static str> {
-        Some("textbox")
+    #[derive(Lens)]
+    struct TestData {
+        text: String,
     }
 
-    fn accessibility(&self, cx: &mut AccessContext, node: &mut AccessNode) {
-        let text_content_id = Entity::new(cx.current.index() as u32 + 3, 0);
-        let bounds = cx.cache.get_bounds(text_content_id);
+    impl Model for TestData {}
 
-        // We need a child node per line
-        // let mut children: Vec<(NodeId, NodeBuilder)> = Vec::new();
-        let node_id = node.node_id();
-        cx.text_context.with_editor(text_content_id, |editor| {
-            let cursor = editor.cursor();
-            let selection = editor.select_opt().unwrap_or(cursor);
+    #[test]
+    fn editing_without_textbox() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
 
-            let mut selection_active_line = node_id;
-            let mut selection_anchor_line = node_id;
-            let mut selection_active_cursor = 0;
-            let mut selection_anchor_cursor = 0;
+        let mut data = TextboxData::with_content(cx, content, "hello world", false);
+        data.select_range(cx, 0..5);
+        data.insert_text(cx, "goodbye");
+        assert_eq!(data.clone_text(cx), "goodbye world");
+        assert_eq!(data.selection_range(cx), 7..7);
+        assert_eq!(data.char_count, 13);
 
-            let mut current_cursor = 0;
-            let mut prev_line_index = std::usize::MAX;
+        data.replace_range(cx, 8..13, "moon");
+        assert_eq!(data.clone_text(cx), "goodbye moon");
+        assert_eq!(data.selection_range(cx), 12..12);
 
-            for (index, line) in editor.buffer().layout_runs().enumerate() {
-                let text = line.text;
+        assert_eq!(data.replace_all(cx, "O", "0", false), 4);
+        assert_eq!(data.clone_text(cx), "g00dbye m00n");
 
-                let mut line_node = AccessNode::new_from_parent(node_id, index);
-                line_node.set_role(Role::InlineTextBox);
+        data.select_all(cx);
+        data.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+        assert_eq!(data.clone_text(cx), "");
+        assert!(data.empty);
+    }
 
-                let line_height = editor.buffer().metrics().line_height as f64;
-                line_node.set_bounds(Rect {
-                    x0: bounds.x as f64,
-                    y0: bounds.y as f64 + line.line_y as f64
-                        - editor.buffer().metrics().font_size as f64,
-                    x1: bounds.x as f64 + line.line_w as f64,
-                    y1: bounds.y as f64 + line.line_y as f64
-                        - editor.buffer().metrics().font_size as f64
-                        + line_height,
-                });
-                line_node.set_text_direction(if line.rtl {
-                    TextDirection::RightToLeft
-                } else {
-                    TextDirection::LeftToRight
-                });
+    #[test]
+    fn edit_timer_replaces_pending_event() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let cx = &mut EventContext::new(&mut cx);
 
-                let mut character_lengths = Vec::with_capacity(line.glyphs.len());
-                let mut character_positions = Vec::with_capacity(line.glyphs.len());
-                let mut character_widths = Vec::with_capacity(line.glyphs.len());
+        let timer = EditTimer::default();
+        let delay = Duration::from_secs(60);
+        timer.schedule(cx, delay, TextEvent::Highlight(1));
+        timer.schedule(cx, delay, TextEvent::Spellcheck(1));
+        timer.schedule(cx, delay, TextEvent::Highlight(2));
+
+        let state = timer.shared.0.lock().unwrap();
+        assert_eq!(state.pending.len(), 2);
+        assert!(state.pending.iter().any(|(_, event)| matches!(event, TextEvent::Highlight(2))));
+    }
+
+    #[test]
+    fn replace_all_is_one_edit() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let mut data = TextboxData::with_content(cx, content, "a-a-b-a", false);
+        let recorded = changes.clone();
+        data.on_change = Some(Arc::new(move |_, delta| recorded.lock().unwrap().push(delta)));
+
+        assert_eq!(data.replace_all(cx, "a", "xy", true), 3);
+        assert_eq!(data.clone_text(cx), "xy-xy-b-xy");
+        assert_eq!(changes.lock().unwrap().len(), 1);
+        assert_eq!(changes.lock().unwrap()[0].range, 0..7);
+        assert_eq!(changes.lock().unwrap()[0].text, "xy-xy-b-xy");
+    }
+
+    #[test]
+    fn counts_follow_multiline_edits() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::with_content(cx, content, "one two\nthree", true);
+        assert_eq!((data.char_count, data.word_count), (13, 3));
+
+        data.replace_range(cx, 3..3, "\nfour five\n");
+        assert_eq!(data.clone_text(cx), "one\nfour five\n two\nthree");
+        assert_eq!((data.char_count, data.word_count), (24, 5));
+
+        data.select_range(cx, 2..15);
+        data.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+        assert_eq!(data.clone_text(cx), "ontwo\nthree");
+        assert_eq!((data.char_count, data.word_count), (11, 2));
+    }
+
+    #[test]
+    fn reset_text_keeps_selection() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::with_content(cx, content, "hello world", false);
+        data.select_range(cx, 6..11);
+        data.reset_text(cx, "hello there");
+        assert_eq!(data.selection_range(cx), 6..11);
+
+        // Offsets are moved back to a character boundary.
+        data.select_range(cx, 2..2);
+        data.reset_text(cx, "h\u{e9}");
+        assert_eq!(data.selection_range(cx), 1..1);
+
+        // Offsets past the end of shorter text are clamped to its end.
+        data.reset_text(cx, "hello world");
+        data.select_range(cx, 6..11);
+        data.reset_text(cx, "hi");
+        assert_eq!(data.selection_range(cx), 2..2);
+    }
+
+    #[test]
+    fn transpose_during_composition() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::with_content(cx, content, "ab", false);
+        data.edit = true;
+        data.select_range(cx, 2..2);
+        data.set_preedit(cx, "に", None);
+        data.event(cx, &mut Event::new(TextEvent::TransposeChars));
+        assert_eq!(data.clone_text(cx), "ba");
+
+        data.set_preedit(cx, "に", None);
+        data.event(cx, &mut Event::new(TextEvent::AcceptSuggestion("abc".to_owned())));
+        assert_eq!(data.clone_text(cx), "abc");
+    }
+
+    #[test]
+    fn events_without_content_are_ignored() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::new();
+        data.event(cx, &mut Event::new(TextEvent::ResetText("hello".to_owned())));
+        data.edit = true;
+        data.event(cx, &mut Event::new(TextEvent::InsertText("a".to_owned())));
+        data.event(cx, &mut Event::new(TextEvent::DeleteText(Movement::Word(Direction::Upstream))));
+        data.event(
+            cx,
+            &mut Event::new(TextEvent::MoveCursor(Movement::Page(Direction::Downstream), false)),
+        );
+        data.event(cx, &mut Event::new(TextEvent::Hit(10.0, 10.0)));
+        data.event(cx, &mut Event::new(TextEvent::Scroll(0.0, 10.0)));
+        data.event(cx, &mut Event::new(TextEvent::TransposeChars));
+        data.event(cx, &mut Event::new(TextEvent::SelectAll));
+        data.event(cx, &mut Event::new(TextEvent::SelectWord));
+        data.event(cx, &mut Event::new(TextEvent::SelectParagraph));
+        data.event(cx, &mut Event::new(TextEvent::AddCursorBelow));
+        data.event(cx, &mut Event::new(TextEvent::TransformSelection(CaseTransform::Upper)));
+        data.event(cx, &mut Event::new(TextEvent::AcceptSuggestion("hello".to_owned())));
+        assert_eq!(data.clone_text(cx), "");
+        assert_eq!(data.with_layout_runs(cx, |runs| runs.count()), 0);
+
+        // The editing state of each textbox lives in a buffer of its own, so none is made for data
+        // without content, where it would be shared by all such data.
+        assert!(!cx.text_context.has_buffer(Entity::null()));
+    }
+
+    #[test]
+    fn dirty_until_submitted() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
 
-                // Get the actual text in the line
-                let first_glyph_pos =
-                    line.glyphs.first().map(|glyph| glyph.start).unwrap_or_default();
-                let last_glyph_pos = line.glyphs.last().map(|glyph| glyph.end).unwrap_or_default();
+        let mut data = TextboxData::with_content(cx, content, "hello", false);
+        data.edit = true;
+        data.event(cx, &mut Event::new(TextEvent::InsertText("!".to_owned())));
+        assert!(data.is_dirty);
 
-                let mut line_text = text[first_glyph_pos..last_glyph_pos].to_owned();
+        data.event(cx, &mut Event::new(TextEvent::Submit(SubmitReason::Enter)));
+        assert!(!data.is_dirty);
 
-                let word_lengths =
-                    line_text.unicode_words().map(|word| word.len() as u8).collect::<Vec<_>>();
+        data.event(cx, &mut Event::new(TextEvent::InsertText("?".to_owned())));
+        data.edit = false;
+        data.event(cx, &mut Event::new(TextEvent::ResetText("bye".to_owned())));
+        assert!(!data.is_dirty);
+    }
 
-                let mut line_length = 0;
+    #[test]
+    fn insert_at_keeps_selection() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let content = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+
+        let mut data = TextboxData::with_content(cx, content, "hello world", false);
+        data.select_between(cx, 8, 6);
+        data.insert_at(cx, 0, "oh, ");
+        assert_eq!(data.clone_text(cx), "oh, hello world");
+        assert_eq!(data.cursor_offsets(cx), (12, 10));
+
+        // Insertions after the selection leave it where it is.
+        data.insert_at(cx, 100, "!");
+        assert_eq!(data.clone_text(cx), "oh, hello world!");
+        assert_eq!(data.cursor_offsets(cx), (12, 10));
+    }
 
-                for glyph in line.glyphs.iter() {
-                    let length = (glyph.end - glyph.start) as u8;
+    #[test]
+    fn enter_does_not_call_on_edit() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
 
-                    line_length += length as usize;
+        let edits = Arc::new(AtomicUsize::new(0));
+        let counter = edits.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_edit(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .entity();
 
-                    let position = glyph.x;
-                    let width = glyph.w;
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                    character_lengths.push(length);
-                    character_positions.push(position);
-                    character_widths.push(width);
-                }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, WindowEvent::KeyDown(Code::Enter, None));
+        while event_manager.flush_events(&mut cx) {}
 
-                // Cosmic strips the newlines but accesskit needs them so we append them back in if line originally ended with a newline
-                // If the last glyph position is equal to the end of the buffer line then this layout run is the last one and ends in a newline.
-                if last_glyph_pos == line.text.len() {
-                    line_text += "\n";
-                    character_lengths.push(1);
-                    character_positions.push(line.line_w);
-                    character_widths.push(0.0);
+        assert_eq!(edits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn on_edit_ref_reads_text_lazily() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let edits = log.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_edit_ref(move |_, text| {
+                let mut edits = edits.lock().unwrap();
+                // Only read the text on every other edit.
+                if edits.len() % 2 == 0 {
+                    edits.push(Some(text()));
+                } else {
+                    edits.push(None);
                 }
+            })
+            .entity();
 
-                // TODO: Might need to append any spaces that were stripped during layout. This can be done by
-                // figuring out if the start of the next line is greater than the end of the current line as long
-                // as the lines have the same `line_i`. This will require a peekable iterator loop.
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                line_node.set_value(line_text.into_boxed_str());
-                line_node.set_character_lengths(character_lengths.into_boxed_slice());
-                line_node.set_character_positions(character_positions.into_boxed_slice());
-                line_node.set_character_widths(character_widths.into_boxed_slice());
-                line_node.set_word_lengths(word_lengths.into_boxed_slice());
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("!".to_owned()));
+        cx.emit_to(textbox, TextEvent::InsertText("?".to_owned()));
+        cx.emit_to(textbox, TextEvent::InsertText(".".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
 
-                if line.line_i != prev_line_index {
-                    current_cursor = 0;
-                }
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![Some("!hello".to_owned()), None, Some("!?.hello".to_owned())]
+        );
+    }
 
-                if line.line_i == cursor.line {
-                    if prev_line_index != line.line_i {
-                        if cursor.index <= line_length {
-                            selection_active_line = line_node.node_id();
-                            selection_active_cursor = cursor.index;
-                        }
-                    } else {
-                        if cursor.index > current_cursor {
-                            selection_active_line = line_node.node_id();
-                            selection_active_cursor = cursor.index - current_cursor;
-                        }
-                    }
-                }
+    #[test]
+    fn expand_selection_to_brackets() {
+        let text = "f(a, [b + c])";
+        let mut range = 6..6;
+        let mut expansions = Vec::new();
+        while let Some(expanded) = enclosing_brackets(text, range) {
+            expansions.push(&text[expanded.clone()]);
+            range = expanded;
+        }
+        assert_eq!(expansions, vec!["b + c", "[b + c]", "a, [b + c]", "(a, [b + c])"]);
+    }
 
-                // Check if the current line contains the cursor or selection
-                // This is a mess because a line happens due to soft and hard breaks but
-                // the cursor and selected indices are relative to the lines caused by hard breaks only.
-                if line.line_i == selection.line {
-                    // A previous line index different to the current means that the current line follows a hard break
-                    if prev_line_index != line.line_i {
-                        if selection.index <= line_length {
-                            selection_anchor_line = line_node.node_id();
-                            selection_anchor_cursor = selection.index;
-                        }
-                    } else {
-                        if selection.index > current_cursor {
-                            selection_anchor_line = line_node.node_id();
-                            selection_anchor_cursor = selection.index - current_cursor;
-                        }
-                    }
-                }
+    #[test]
+    fn on_change_reports_deltas() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let deltas = log.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_change(move |_, delta| deltas.lock().unwrap().push(delta))
+            .entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("!".to_owned()));
+        cx.emit_to(textbox, TextEvent::SelectAll);
+        cx.emit_to(textbox, TextEvent::InsertText("hi".to_owned()));
+        cx.emit_to(textbox, TextEvent::DeleteText(Movement::Grapheme(Direction::Upstream)));
+        while event_manager.flush_events(&mut cx) {}
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                EditDelta { range: 0..0, text: "!".to_owned() },
+                EditDelta { range: 0..6, text: "hi".to_owned() },
+                EditDelta { range: 1..2, text: String::new() },
+            ]
+        );
+    }
 
-                node.add_child(line_node);
+    #[test]
+    fn batch_notifies_once() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        let log = edits.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_edit(move |_, text| log.lock().unwrap().push(text))
+            .entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(
+            textbox,
+            TextEvent::Batch(vec![
+                TextEvent::InsertText("a".to_owned()),
+                TextEvent::InsertText("b".to_owned()),
+                TextEvent::Batch(vec![TextEvent::InsertText("c".to_owned())]),
+            ]),
+        );
+        while event_manager.flush_events(&mut cx) {}
+
+        assert_eq!(*edits.lock().unwrap(), vec!["abchello".to_owned()]);
+    }
 
-                current_cursor += line_length;
-                prev_line_index = line.line_i;
-            }
+    #[test]
+    fn unselectable_textbox_ignores_selection() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let textbox = Textbox::new(&mut cx, TestData::text).selectable(false);
+        let content = textbox.content_entity();
+        let textbox = textbox.entity();
 
-            node.set_text_selection(TextSelection {
-                anchor: TextPosition {
-                    node: selection_anchor_line,
-                    character_index: selection_anchor_cursor,
-                },
-                focus: TextPosition {
-                    node: selection_active_line,
-                    character_index: selection_active_cursor,
-                },
-            });
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-            match self.kind {
-                TextboxKind::MultiLineUnwrapped | TextboxKind::MultiLineWrapped => {
-                    node.node_builder.set_multiline();
-                }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::SelectAll);
+        while event_manager.flush_events(&mut cx) {}
 
-                _ => {
-                    node.node_builder.clear_multiline();
-                }
-            }
+        assert!(!cx.style.pseudo_classes.get(textbox).unwrap().contains(PseudoClass::CHECKED));
+        let selection = cx.text_context.with_editor(content, |buf| buf.copy_selection());
+        assert_eq!(selection, None);
+    }
 
-            node.node_builder.set_default_action_verb(DefaultActionVerb::Focus);
-        });
+    #[test]
+    fn handle_returns_inner_entities() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+
+        let textbox = Textbox::new(&mut cx, TestData::text);
+        let container = textbox.container_entity();
+        let content = textbox.content_entity();
+        assert_eq!(content.parent(&cx.tree), Some(container));
+        assert_eq!(
+            cx.text_context.with_buffer(content, |buf| buf.lines[0].text().to_owned()),
+            "hello"
+        );
     }
 
-    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|window_event, _| match window_event {
-            WindowEvent::MouseDown(MouseButton::Left) => {
-                if cx.is_over() {
-                    cx.focus_with_visibility(false);
-                    cx.capture();
-                    cx.set_checked(true);
-                    cx.lock_cursor_icon();
+    #[test]
+    fn selection_change() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
 
-                    cx.emit(TextEvent::Hit(cx.mouse.cursorx, cx.mouse.cursory));
-                } else {
-                    cx.emit(TextEvent::Submit(false));
-                    if let Some(source) = cx.data::<L::Source>() {
-                        let text = self.lens.view(source, |t| {
-                            if let Some(t) = t {
-                                t.to_string()
-                            } else {
-                                "".to_owned()
-                            }
-                        });
+        let selections = Arc::new(Mutex::new(Vec::new()));
+        let log = selections.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_selection_change(move |_, selection| log.lock().unwrap().push(selection))
+            .entity();
 
-                        cx.emit(TextEvent::ResetText(text));
-                    };
-                    cx.release();
-                    cx.set_checked(false);
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                    // Forward event to hovered
-                    cx.event_queue.push_back(
-                        Event::new(WindowEvent::MouseDown(MouseButton::Left)).target(cx.hovered()),
-                    );
-                    cx.event_queue.push_back(
-                        Event::new(WindowEvent::PressDown { mouse: true }).target(cx.hovered()),
-                    );
-                }
-            }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::SelectAll);
+        cx.emit_to(textbox, TextEvent::SelectAll);
+        cx.emit_to(textbox, TextEvent::EndEdit);
+        while event_manager.flush_events(&mut cx) {}
 
-            WindowEvent::FocusIn => {
-                if cx.mouse.left.pressed != cx.current()
-                    || cx.mouse.left.state == MouseButtonState::Released
-                {
-                    cx.emit(TextEvent::StartEdit);
-                }
-            }
+        assert_eq!(*selections.lock().unwrap(), vec![Some((0, 5)), None]);
+    }
 
-            WindowEvent::FocusOut => {
-                cx.emit(TextEvent::EndEdit);
-            }
+    #[test]
+    fn line_ending_of_loaded_text_is_kept() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "one\r\ntwo".to_owned() }.build(&mut cx);
 
-            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
-                cx.emit(TextEvent::SelectWord);
-            }
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        let log = edits.clone();
+        let textbox = Textbox::new_multiline(&mut cx, TestData::text, true)
+            .on_edit(move |_, text| log.lock().unwrap().push(text))
+            .entity();
 
-            WindowEvent::MouseTripleClick(MouseButton::Left) => {
-                cx.emit(TextEvent::SelectParagraph);
-            }
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-            WindowEvent::MouseUp(MouseButton::Left) => {
-                cx.unlock_cursor_icon();
-                if cx.mouse.left.pressed == cx.current() {
-                    cx.emit(TextEvent::StartEdit);
-                }
-            }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("!".to_owned()));
+        cx.emit_to(textbox, TextEvent::SetLineEnding(LineEnding::Lf));
+        cx.emit_to(textbox, TextEvent::InsertText("?".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
 
-            WindowEvent::MouseMove(_, _) => {
-                if cx.mouse.left.state == MouseButtonState::Pressed
-                    && cx.mouse.left.pressed == cx.current
-                {
-                    cx.emit(TextEvent::Drag(cx.mouse.cursorx, cx.mouse.cursory));
-                }
-            }
+        assert_eq!(*edits.lock().unwrap(), vec!["!one\r\ntwo".to_owned(), "!?one\ntwo".to_owned()]);
+    }
 
-            WindowEvent::MouseScroll(x, y) => {
-                cx.emit(TextEvent::Scroll(*x, *y));
-            }
+    #[test]
+    fn indent_selected_lines() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "one\n\ttwo\nthree", true);
+        data.edit = true;
+
+        // The selection ends at the start of the last line, which is left alone.
+        data.select_range(cx, 1..9);
+        data.event(cx, &mut Event::new(TextEvent::Indent));
+        assert_eq!(data.clone_text(cx), "    one\n    \ttwo\nthree");
+        assert_eq!(data.selection_range(cx), 5..17);
+
+        data.event(cx, &mut Event::new(TextEvent::Outdent));
+        data.event(cx, &mut Event::new(TextEvent::Outdent));
+        assert_eq!(data.clone_text(cx), "one\ntwo\nthree");
+        assert_eq!(data.selection_range(cx), 1..8);
+    }
 
-            WindowEvent::CharInput(c) => {
-                if *c != '\u{1b}' && // Escape
-                            *c != '\u{8}' && // Backspace
-                            *c != '\u{9}' && // Tab
-                            *c != '\u{7f}' && // Delete
-                            *c != '\u{0d}' && // Carriage return
-                            !cx.modifiers.contains(Modifiers::CTRL)
-                {
-                    cx.emit(TextEvent::InsertText(String::from(*c)));
-                }
-            }
+    #[test]
+    fn toggle_line_comment() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data =
+            TextboxData::with_content(cx, entity, "fn a() {\n\n    // b\n    c\n}", true);
+        data.edit = true;
+
+        // Only some of the lines are commented, so all of them are commented.
+        data.select_range(cx, 10..23);
+        data.event(cx, &mut Event::new(TextEvent::ToggleLineComment("//".to_owned())));
+        assert_eq!(data.clone_text(cx), "fn a() {\n\n    // // b\n    // c\n}");
+
+        data.event(cx, &mut Event::new(TextEvent::ToggleLineComment("//".to_owned())));
+        assert_eq!(data.clone_text(cx), "fn a() {\n\n    // b\n    c\n}");
+    }
 
-            WindowEvent::KeyDown(code, _) => match code {
-                Code::Enter => {
-                    // Finish editing
-                    if matches!(self.kind, TextboxKind::SingleLine) {
-                        cx.emit(TextEvent::Submit(true));
-                        if let Some(source) = cx.data::<L::Source>() {
-                            let text = self.lens.view(source, |t| {
-                                if let Some(t) = t {
-                                    t.to_string()
-                                } else {
-                                    "".to_owned()
-                                }
-                            });
+    #[test]
+    fn duplicate_line() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "a\nbc\nd", true);
+        data.edit = true;
+
+        data.select_range(cx, 3..3);
+        data.event(cx, &mut Event::new(TextEvent::DuplicateLine));
+        assert_eq!(data.clone_text(cx), "a\nbc\nbc\nd");
+        assert_eq!(data.selection_range(cx), 6..6);
+
+        // Each line covered by the selection is copied.
+        data.select_range(cx, 1..4);
+        data.event(cx, &mut Event::new(TextEvent::DuplicateLine));
+        assert_eq!(data.clone_text(cx), "a\nbc\na\nbc\nbc\nd");
+        assert_eq!(data.selection_range(cx), 6..9);
+    }
 
-                            cx.emit(TextEvent::SelectAll);
-                            cx.emit(TextEvent::InsertText(text));
-                        };
+    #[test]
+    fn move_lines() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "a\nbc\nd", true);
+        data.edit = true;
+
+        data.select_range(cx, 3..3);
+        data.event(cx, &mut Event::new(TextEvent::MoveLineUp));
+        assert_eq!(data.clone_text(cx), "bc\na\nd");
+        assert_eq!(data.selection_range(cx), 1..1);
+
+        // There is no line above the first to swap with.
+        data.event(cx, &mut Event::new(TextEvent::MoveLineUp));
+        assert_eq!(data.clone_text(cx), "bc\na\nd");
+
+        data.select_range(cx, 1..4);
+        data.event(cx, &mut Event::new(TextEvent::MoveLineDown));
+        assert_eq!(data.clone_text(cx), "d\nbc\na");
+        assert_eq!(data.selection_range(cx), 3..6);
+    }
 
-                        cx.set_checked(false);
-                        cx.release();
-                    } else {
-                        cx.emit(TextEvent::InsertText("\n".to_owned()));
-                    }
-                }
+    #[test]
+    fn font_reaches_content() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let content = Textbox::new(&mut cx, TestData::text)
+            .font_family(vec![FamilyOwned::Monospace])
+            .font_weight(Weight::BOLD)
+            .content_entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_style_updates();
+
+        // The text of the content is laid out with the font of the textbox.
+        assert_eq!(cx.style.font_family.get(content), Some(&vec![FamilyOwned::Monospace]));
+        assert_eq!(cx.style.font_weight.get(content), Some(&Weight::BOLD));
+    }
 
-                Code::ArrowLeft => {
-                    let movement = if cx.modifiers.contains(Modifiers::CTRL) {
-                        Movement::Word(Direction::Left)
-                    } else {
-                        Movement::Grapheme(Direction::Left)
-                    };
+    #[test]
+    fn delete_grapheme_clusters() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let text = format!("e\u{301}{}\u{928}\u{93f}", family);
+        let mut data = TextboxData::with_content(cx, entity, &text, false);
+
+        // Devanagari letter with a dependent vowel sign.
+        data.select_range(cx, text.len()..text.len());
+        data.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+        assert_eq!(data.clone_text(cx), format!("e\u{301}{}", family));
+
+        // Family emoji joined by zero-width joiners.
+        data.delete_text(cx, Movement::Grapheme(Direction::Upstream));
+        assert_eq!(data.clone_text(cx), "e\u{301}");
+
+        // Letter with a combining accent.
+        data.select_range(cx, 0..0);
+        data.delete_text(cx, Movement::Grapheme(Direction::Downstream));
+        assert_eq!(data.clone_text(cx), "");
+    }
 
-                    cx.emit(TextEvent::MoveCursor(
-                        movement,
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+    #[test]
+    fn keyboard_focus_is_visible() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let textbox = Textbox::new(&mut cx, TestData::text).entity();
 
-                Code::ArrowRight => {
-                    let movement = if cx.modifiers.contains(Modifiers::CTRL) {
-                        Movement::Word(Direction::Right)
-                    } else {
-                        Movement::Grapheme(Direction::Right)
-                    };
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                    cx.emit(TextEvent::MoveCursor(
-                        movement,
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+        cx.emit_to(textbox, WindowEvent::FocusIn);
+        while event_manager.flush_events(&mut cx) {}
 
-                Code::ArrowUp => {
-                    cx.emit(TextEvent::MoveCursor(
-                        Movement::Line(Direction::Upstream),
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+        let classes = cx.style.pseudo_classes.get(textbox).unwrap();
+        assert!(classes.contains(PseudoClass::CHECKED));
+        assert!(classes.contains(PseudoClass::FOCUS_VISIBLE));
+    }
 
-                Code::ArrowDown => {
-                    cx.emit(TextEvent::MoveCursor(
-                        Movement::Line(Direction::Downstream),
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+    #[test]
+    fn move_by_paragraph() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "one\ntwo\nthree", true);
+
+        data.select_range(cx, 5..5);
+        data.move_cursor(cx, Movement::Paragraph(Direction::Upstream), false);
+        assert_eq!(data.selection_range(cx), 4..4);
+        data.move_cursor(cx, Movement::Paragraph(Direction::Upstream), false);
+        assert_eq!(data.selection_range(cx), 0..0);
+        data.move_cursor(cx, Movement::Paragraph(Direction::Downstream), true);
+        assert_eq!(data.selection_range(cx), 0..4);
+        data.move_cursor(cx, Movement::Paragraph(Direction::Downstream), false);
+        data.move_cursor(cx, Movement::Paragraph(Direction::Downstream), false);
+        assert_eq!(data.selection_range(cx), 13..13);
+    }
 
-                Code::Backspace => {
-                    if cx.modifiers.contains(Modifiers::CTRL) {
-                        cx.emit(TextEvent::DeleteText(Movement::Word(Direction::Upstream)));
-                    } else {
-                        cx.emit(TextEvent::DeleteText(Movement::Grapheme(Direction::Upstream)));
-                    }
-                }
+    #[test]
+    fn escape_calls_on_cancel() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
 
-                Code::Delete => {
-                    if cx.modifiers.contains(Modifiers::CTRL) {
-                        cx.emit(TextEvent::DeleteText(Movement::Word(Direction::Downstream)));
-                    } else {
-                        cx.emit(TextEvent::DeleteText(Movement::Grapheme(Direction::Downstream)));
-                    }
-                }
+        let cancels = Arc::new(AtomicUsize::new(0));
+        let counter = cancels.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_cancel(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .entity();
 
-                Code::Escape => {
-                    cx.emit(TextEvent::EndEdit);
-                    cx.set_checked(false);
-                }
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                Code::Home => {
-                    cx.emit(TextEvent::MoveCursor(
-                        Movement::LineStart,
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, WindowEvent::KeyDown(Code::Escape, None));
+        while event_manager.flush_events(&mut cx) {}
 
-                Code::End => {
-                    cx.emit(TextEvent::MoveCursor(
-                        Movement::LineEnd,
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+        assert_eq!(cancels.load(Ordering::SeqCst), 1);
+        assert!(!cx.style.pseudo_classes.get(textbox).unwrap().contains(PseudoClass::CHECKED));
+    }
 
-                Code::PageUp | Code::PageDown => {
-                    let direction = if *code == Code::PageUp {
-                        Direction::Upstream
-                    } else {
-                        Direction::Downstream
-                    };
-                    cx.emit(TextEvent::MoveCursor(
-                        if cx.modifiers.contains(Modifiers::CTRL) {
-                            Movement::Body(direction)
-                        } else {
-                            Movement::Page(direction)
-                        },
-                        cx.modifiers.contains(Modifiers::SHIFT),
-                    ));
-                }
+    #[test]
+    fn disabled_textbox_ignores_mouse_down() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
 
-                Code::KeyA => {
-                    if cx.modifiers.contains(Modifiers::CTRL) {
-                        cx.emit(TextEvent::SelectAll);
-                    }
-                }
+        let textbox = Textbox::new(&mut cx, TestData::text).disabled(true).entity();
 
-                Code::KeyC if cx.modifiers == &Modifiers::CTRL => {
-                    cx.emit(TextEvent::Copy);
-                }
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                Code::KeyV if cx.modifiers == &Modifiers::CTRL => {
-                    cx.emit(TextEvent::Paste);
-                }
+        let focused = cx.focused;
+        cx.hovered = textbox;
+        if let Some(pseudo_classes) = cx.style.pseudo_classes.get_mut(textbox) {
+            pseudo_classes.set(PseudoClass::OVER, true);
+        }
+        cx.emit_to(textbox, WindowEvent::MouseDown(MouseButton::Left));
+        while event_manager.flush_events(&mut cx) {}
 
-                Code::KeyX if cx.modifiers == &Modifiers::CTRL => {
-                    cx.emit(TextEvent::Cut);
-                }
+        assert_eq!(cx.captured, Entity::null());
+        assert_eq!(cx.focused, focused);
+        assert!(!cx.style.pseudo_classes.get(textbox).unwrap().contains(PseudoClass::CHECKED));
+    }
 
-                _ => {}
-            },
+    #[test]
+    fn inactive_selection_color() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        cx.add_theme("textbox { selection-color-inactive: #ff0000; }");
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let content = Textbox::new(&mut cx, TestData::text).content_entity();
+
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
+        BackendContext::new(&mut cx).process_style_updates();
+
+        // The text label inherits the color from the textbox.
+        cx.current = content;
+        let cx = DrawContext::new(&mut cx);
+        assert_eq!(cx.selection_color_inactive(), Some(&Color::rgb(255, 0, 0)));
+    }
 
-            WindowEvent::ActionRequest(ActionRequest {
-                action: accesskit::Action::SetTextSelection,
-                target: _,
-                data: Some(ActionData::SetTextSelection(selection)),
-            }) => {
-                // TODO: This needs testing once I figure out how to trigger it with a screen reader.
-                let text_content_id = Entity::new(cx.current.index() as u32 + 3, 0);
-                let node_id = cx.current.accesskit_id();
-                cx.text_context.with_editor(text_content_id, |editor| {
-                    // let cursor_node = selection.focus.node;
-                    let selection_node = selection.anchor.node;
-
-                    // let mut cursor_line_index = 0;
-                    // let mut cursor_index = 0;
-                    let mut selection_line_index = 0;
-                    let mut selection_index = 0;
-
-                    let mut current_cursor = 0;
-                    let mut prev_line_index = std::usize::MAX;
-
-                    for (index, line) in editor.buffer().layout_runs().enumerate() {
-                        let line_node = AccessNode::new_from_parent(node_id, index);
-                        // if line_node.node_id() == cursor_node {
-                        //     cursor_line_index = line.line_i;
-                        //     cursor_index = selection.focus.character_index + current_cursor;
-                        // }
-
-                        if line_node.node_id() == selection_node {
-                            selection_line_index = line.line_i;
-                            selection_index = selection.anchor.character_index + current_cursor;
-                        }
+    #[test]
+    fn reset_text_while_editing() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
 
-                        if line.line_i != prev_line_index {
-                            current_cursor = 0;
-                        }
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        let log = edits.clone();
+        let textbox = Textbox::new(&mut cx, TestData::text)
+            .on_edit(move |_, text| log.lock().unwrap().push(text))
+            .entity();
 
-                        let first_glyph_pos =
-                            line.glyphs.first().map(|glyph| glyph.start).unwrap_or_default();
-                        let last_glyph_pos =
-                            line.glyphs.last().map(|glyph| glyph.end).unwrap_or_default();
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                        let line_length = last_glyph_pos - first_glyph_pos;
+        cx.emit_to(textbox, TextEvent::StartEdit);
+        cx.emit_to(textbox, TextEvent::InsertText("!".to_owned()));
+        cx.emit_to(textbox, TextEvent::ResetText("goodbye".to_owned()));
+        cx.emit_to(textbox, TextEvent::InsertText("?".to_owned()));
+        while event_manager.flush_events(&mut cx) {}
 
-                        current_cursor += line_length;
-                        prev_line_index = line.line_i;
-                    }
+        assert_eq!(edits.lock().unwrap().last().map(String::as_str), Some("!?hello"));
+    }
 
-                    let selection_cursor = Cursor::new(selection_line_index, selection_index);
-                    editor.set_select_opt(Some(selection_cursor));
+    #[test]
+    fn set_text() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        TestData { text: "hello".to_owned() }.build(&mut cx);
+        let content = Textbox::new(&mut cx, TestData::text).set_text("seeded").content_entity();
 
-                    // TODO: Either add a method to set the cursor by index to cosmic,
-                    // or loop over an `Action` to move the cursor to the correct place.
-                });
+        let mut event_manager = EventManager::new();
+        while event_manager.flush_events(&mut cx) {}
 
-                // println!("Select some text: {:?}", selection);
-            }
+        let text = cx.text_context.with_buffer(content, |buf| buf.lines[0].text().to_owned());
+        assert_eq!(text, "seeded");
+    }
 
-            _ => {}
-        });
+    #[test]
+    fn merge_overlapping_cursors() {
+        // Overlapping selections become one, which keeps the direction of the primary caret.
+        assert_eq!(merge_cursors(vec![(0, 3), (2, 5), (7, 7)], 1), ((0, 5), vec![(7, 7)]));
+        assert_eq!(merge_cursors(vec![(5, 2), (4, 4), (8, 8)], 0), ((5, 2), vec![(8, 8)]));
+        // Carets without a selection merge with selections they touch, selections don't.
+        assert_eq!(merge_cursors(vec![(0, 2), (2, 2), (2, 4)], 2), ((2, 4), vec![(0, 2)]));
+        assert_eq!(merge_cursors(vec![(3, 3), (1, 1), (3, 3)], 0), ((3, 3), vec![(1, 1)]));
     }
-}
 
-// can't just be a stack because what if you've styled stacks
-pub struct TextboxContainer {}
-impl View for TextboxContainer {
-    fn element(&self) -> Option<&'static str> {
-        Some("textboxcontainer")
+    #[test]
+    fn edit_at_every_cursor() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "ab\ncd\nef", true);
+        data.edit = true;
+
+        data.select_range(cx, 1..1);
+        data.add_cursor(cx, Direction::Downstream);
+        data.add_cursor(cx, Direction::Downstream);
+        data.event(cx, &mut Event::new(TextEvent::InsertText("X".to_owned())));
+        assert_eq!(data.clone_text(cx), "aXb\ncXd\neXf");
+        assert_eq!(data.cursor_offsets(cx), (2, 2));
+        assert_eq!(data.extra_cursors, vec![(6, 6), (10, 10)]);
+
+        data.event(cx, &mut Event::new(TextEvent::Indent));
+        assert_eq!(data.clone_text(cx), "aX    b\ncX    d\neX    f");
+
+        let backspace = Movement::Grapheme(Direction::Upstream);
+        data.event(cx, &mut Event::new(TextEvent::DeleteText(backspace)));
+        assert_eq!(data.clone_text(cx), "aX   b\ncX   d\neX   f");
     }
-}
 
-// can't just be a label because what if you've styled labels
-pub struct TextboxLabel {}
-impl View for TextboxLabel {
-    fn element(&self) -> Option<&'static str> {
-        Some("textboxlabel")
+    #[test]
+    fn cursors_merge_after_edit() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "abcdef", false);
+        data.edit = true;
+
+        // Deleting forwards from 2 and 3 leaves both carets at 2.
+        data.select_range(cx, 2..2);
+        data.set_extra_cursors(cx, vec![(3, 3)]);
+        let delete = Movement::Grapheme(Direction::Downstream);
+        data.event(cx, &mut Event::new(TextEvent::DeleteText(delete)));
+        assert_eq!(data.clone_text(cx), "abef");
+        assert_eq!(data.cursor_offsets(cx), (2, 2));
+        assert!(data.extra_cursors.is_empty());
+        assert!(cx.text_context.extra_cursors(data.content_entity).is_empty());
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn copy_and_paste_every_selection() {
+        let mut cx = Context::new(WindowSize::new(100, 100), 1.0);
+        let entity = Element::new(&mut cx).entity();
+        let cx = &mut EventContext::new(&mut cx);
+        let mut data = TextboxData::with_content(cx, entity, "ab\ncd\nef", true);
+        data.edit = true;
+
+        data.select_range(cx, 6..8);
+        data.set_extra_cursors(cx, vec![(0, 2)]);
+        assert_eq!(data.clone_selections(cx).as_deref(), Some("ab\nef"));
+
+        // As many lines as carets are pasted one per caret, in the order of the text.
+        data.paste(cx, "1\n2".to_owned());
+        assert_eq!(data.clone_text(cx), "1\ncd\n2");
+
+        data.delete_ranges(cx, vec![0..2, 1..5]);
+        assert_eq!(data.clone_text(cx), "2");
     }
 }
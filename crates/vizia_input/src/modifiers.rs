@@ -10,3 +10,13 @@ bitflags! {
         const LOGO = 1<<3;
     }
 }
+
+/// Returns the modifier used for application shortcuts such as copy and paste, which is Cmd on
+/// macOS and Ctrl everywhere else.
+pub fn command_modifier() -> Modifiers {
+    if cfg!(target_os = "macos") {
+        Modifiers::LOGO
+    } else {
+        Modifiers::CTRL
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{CursorIcon, Position, WindowSize};
 use morphorm::GeometryChanged;
+use std::path::PathBuf;
 use vizia_input::{Code, Key, MouseButton};
 
 /// Events generated by the application in response to OS events as well as events that can be used
@@ -28,6 +29,12 @@ pub enum WindowEvent {
     },
     /// Emitted when the mouse cursor is moved
     MouseMove(f32, f32),
+    /// Emitted to the hovered view while data is dragged over it.
+    DragOver(DropData),
+    /// Emitted to the hovered view when a drag leaves it without dropping.
+    DragLeave,
+    /// Emitted to the hovered view when data is dropped onto it.
+    Drop(DropData),
     /// Emitted when the mouse scroll wheel is scrolled.
     MouseScroll(f32, f32),
     /// Emitted when the mouse cursor enters the bounding box of an entity.
@@ -44,6 +51,12 @@ pub enum WindowEvent {
     FocusOut,
     /// Emitted when a character is typed.
     CharInput(char),
+    /// Emitted while an input method is composing text, with the text being composed and the byte
+    /// range of the cursor within it, or `None` if the cursor should be hidden. An empty string
+    /// ends the composition.
+    ImePreedit(String, Option<(usize, usize)>),
+    /// Emitted when an input method commits composed text.
+    ImeCommit(String),
     /// Emitted when a keyboard key is pressed.
     KeyDown(Code, Option<Key>),
     /// Emitted when a keyboard key is released.
@@ -56,6 +69,11 @@ pub enum WindowEvent {
     SetCursorPosition(u32, u32),
     /// Sets the title of the window.
     SetTitle(String),
+    /// Sets whether the window accepts input from input methods.
+    SetImeAllowed(bool),
+    /// Sets the (x,y) position, in physical window coordinates, at which an input method shows its
+    /// candidate window.
+    SetImePosition(f32, f32),
     /// Sets the size of the window.
     SetSize(WindowSize),
     /// Sets the position of the window.
@@ -83,6 +101,9 @@ pub enum WindowEvent {
     // TODO: check if this includes margins + borders.
     /// Emitted when an entity changes position or size.
     GeometryChanged(GeometryChanged),
+    /// Emitted to every view when the DPI factor of the window changes, for example when the
+    /// window is moved to a monitor with a different scale factor.
+    ScaleFactorChanged,
     /// Requests a redraw of the window contents.
     Redraw,
     /// Request a restyle.
@@ -95,3 +116,14 @@ pub enum WindowEvent {
     /// Reloads all application stylesheets.
     ReloadStyles,
 }
+
+/// The data carried by a drag and drop operation.
+///
+/// This type is part of the prelude.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DropData {
+    /// Plain text, for example dragged from another view.
+    Text(String),
+    /// A file dragged from outside of the application.
+    File(PathBuf),
+}
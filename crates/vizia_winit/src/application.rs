@@ -217,9 +217,11 @@ impl Application {
         ))]
         unsafe {
             if let Some(display) = window.window().wayland_display() {
-                let (_, clipboard) =
+                let (primary_selection, clipboard) =
                     copypasta::wayland_clipboard::create_clipboards_from_external(display);
-                BackendContext::new(&mut context).set_clipboard_provider(Box::new(clipboard));
+                let mut cx = BackendContext::new(&mut context);
+                cx.set_clipboard_provider(Box::new(clipboard));
+                cx.set_primary_selection_provider(Box::new(primary_selection));
             }
         }
 
@@ -391,7 +393,7 @@ impl Application {
                             scale_factor,
                             new_inner_size,
                         } => {
-                            cx.style().dpi_factor = scale_factor;
+                            cx.set_dpi_factor(scale_factor);
                             cx.cache().set_width(Entity::root(), new_inner_size.width as f32);
                             cx.cache().set_height(Entity::root(), new_inner_size.height as f32);
 
@@ -494,6 +496,28 @@ impl Application {
                             cx.emit_origin(WindowEvent::CharInput(character));
                         }
 
+                        winit::event::WindowEvent::Ime(ime) => match ime {
+                            winit::event::Ime::Preedit(text, cursor) => {
+                                cx.emit_origin(WindowEvent::ImePreedit(text, cursor));
+                            }
+                            winit::event::Ime::Commit(text) => {
+                                cx.emit_origin(WindowEvent::ImeCommit(text));
+                            }
+                            _ => {}
+                        },
+
+                        winit::event::WindowEvent::HoveredFile(path) => {
+                            cx.emit_origin(WindowEvent::DragOver(DropData::File(path)));
+                        }
+
+                        winit::event::WindowEvent::HoveredFileCancelled => {
+                            cx.emit_origin(WindowEvent::DragLeave);
+                        }
+
+                        winit::event::WindowEvent::DroppedFile(path) => {
+                            cx.emit_origin(WindowEvent::Drop(DropData::File(path)));
+                        }
+
                         winit::event::WindowEvent::Resized(physical_size) => {
                             if let Some(mut window_view) = cx.views().remove(&Entity::root()) {
                                 if let Some(window) = window_view.downcast_mut::<Window>() {
@@ -244,6 +244,14 @@ impl View for Window {
                 self.window().set_title(title);
             }
 
+            WindowEvent::SetImeAllowed(flag) => {
+                self.window().set_ime_allowed(*flag);
+            }
+
+            WindowEvent::SetImePosition(x, y) => {
+                self.window().set_ime_position(PhysicalPosition::new(*x, *y));
+            }
+
             WindowEvent::SetSize(size) => {
                 self.window().set_inner_size(LogicalSize::new(size.width, size.height));
             }
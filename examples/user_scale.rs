@@ -27,9 +27,9 @@ fn app_main(cx: &mut Context) {
                 .child_space(Stretch(1.0))
                 .bottom(Pixels(5.0))
                 .top(Pixels(-5.0));
-            Textbox::new(cx, AppData::user_scale_factor).width(Percentage(100.0)).on_submit(
-                |cx, value, success| {
-                    if success {
+            Textbox::new(cx, AppData::user_scale_factor).width(Percentage(100.0)).on_submit_ex(
+                |cx, value, reason| {
+                    if reason == SubmitReason::Enter {
                         if let Ok(factor) = value.parse() {
                             cx.set_user_scale_factor(factor);
                         }
@@ -48,8 +48,8 @@ fn app_main(cx: &mut Context) {
                     .map(|WindowSize { width, height }| format!("{width}x{height}")),
             )
             .width(Percentage(100.0))
-            .on_submit(|cx, value, success| {
-                if success {
+            .on_submit_ex(|cx, value, reason| {
+                if reason == SubmitReason::Enter {
                     let parsed = value
                         .split_once('x')
                         .map(|(width, height)| (width.parse(), height.parse()));